@@ -241,6 +241,10 @@ pub fn update_concentration_coef(amm: &mut AMM, scale: u128) -> DriftResult {
     let new_concentration_coef =
         CONCENTRATION_PRECISION + (MAX_CONCENTRATION_COEFFICIENT - CONCENTRATION_PRECISION) / scale;
 
+    set_concentration_coef(amm, new_concentration_coef)
+}
+
+pub fn set_concentration_coef(amm: &mut AMM, new_concentration_coef: u128) -> DriftResult {
     validate!(
         new_concentration_coef > CONCENTRATION_PRECISION
             && new_concentration_coef <= MAX_CONCENTRATION_COEFFICIENT,
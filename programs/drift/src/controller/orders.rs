@@ -1306,7 +1306,7 @@ fn get_maker_orders_info(
     jit_maker_order_id: Option<u32>,
     now: i64,
     slot: u64,
-) -> DriftResult<Vec<(Pubkey, usize, u64)>> {
+) -> DriftResult<Vec<(Pubkey, usize, u64, u64)>> {
     let maker_direction = taker_order.direction.opposite();
 
     let mut maker_orders_info = Vec::with_capacity(16);
@@ -1428,9 +1428,17 @@ fn get_maker_orders_info(
                 continue;
             }
 
+            let maker_base_asset_amount =
+                maker.orders[maker_order_index].get_base_asset_amount_unfilled(None)?;
+
             insert_maker_order_info(
                 &mut maker_orders_info,
-                (*maker_key, maker_order_index, maker_order_price),
+                (
+                    *maker_key,
+                    maker_order_index,
+                    maker_order_price,
+                    maker_base_asset_amount,
+                ),
                 maker_direction,
             );
         }
@@ -1441,8 +1449,8 @@ fn get_maker_orders_info(
 
 #[inline(always)]
 fn insert_maker_order_info(
-    maker_orders_info: &mut Vec<(Pubkey, usize, u64)>,
-    maker_order_info: (Pubkey, usize, u64),
+    maker_orders_info: &mut Vec<(Pubkey, usize, u64, u64)>,
+    maker_order_info: (Pubkey, usize, u64, u64),
     direction: PositionDirection,
 ) {
     let price = maker_order_info.2;
@@ -1511,7 +1519,7 @@ fn fulfill_perp_order(
     user_stats: &mut UserStats,
     makers_and_referrer: &UserMap,
     makers_and_referrer_stats: &UserStatsMap,
-    maker_orders_info: &[(Pubkey, usize, u64)],
+    maker_orders_info: &[(Pubkey, usize, u64, u64)],
     filler: &mut Option<&mut User>,
     filler_key: &Pubkey,
     filler_stats: &mut Option<&mut UserStats>,
@@ -1552,6 +1560,7 @@ fn fulfill_perp_order(
             Some(oracle_price),
             limit_price,
             amm_is_available,
+            now,
             slot,
             min_auction_duration,
         )?
@@ -296,7 +296,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -485,7 +490,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 + BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -682,7 +692,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 * 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -878,7 +893,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 * taker_mul as u64,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1082,7 +1102,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1295,7 +1320,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1506,7 +1536,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1740,7 +1775,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1945,7 +1985,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2132,7 +2177,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 10 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                10 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2331,7 +2381,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 200 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                200 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2582,7 +2637,7 @@ pub mod amm_jit {
                 &mut taker_stats,
                 &makers_and_referrers,
                 &maker_and_referrer_stats,
-                &[(maker_key, 0, auction_price)],
+                &[(maker_key, 0, auction_price, baa as u64)],
                 &mut Some(&mut filler),
                 &filler_key,
                 &mut Some(&mut filler_stats),
@@ -2866,7 +2921,7 @@ pub mod amm_jit {
                 &mut taker_stats,
                 &makers_and_referrers,
                 &maker_and_referrer_stats,
-                &[(maker_key, 0, auction_price)],
+                &[(maker_key, 0, auction_price, baa as u64)],
                 &mut Some(&mut filler),
                 &filler_key,
                 &mut Some(&mut filler_stats),
@@ -3094,7 +3149,12 @@ pub mod amm_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 10 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                10 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -651,7 +651,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -853,7 +858,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 + BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1062,7 +1072,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 * 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1278,7 +1293,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 * 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1497,7 +1517,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 * taker_mul as u64,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1704,7 +1729,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -1920,7 +1950,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2128,7 +2163,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2318,7 +2358,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 10 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                10 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2520,7 +2565,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 200 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                200 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2771,7 +2821,7 @@ pub mod amm_lp_jit {
                 &mut taker_stats,
                 &makers_and_referrers,
                 &maker_and_referrer_stats,
-                &[(maker_key, 0, auction_price)],
+                &[(maker_key, 0, auction_price, baa as u64)],
                 &mut Some(&mut filler),
                 &filler_key,
                 &mut Some(&mut filler_stats),
@@ -3055,7 +3105,7 @@ pub mod amm_lp_jit {
                 &mut taker_stats,
                 &makers_and_referrers,
                 &maker_and_referrer_stats,
-                &[(maker_key, 0, auction_price)],
+                &[(maker_key, 0, auction_price, baa as u64)],
                 &mut Some(&mut filler),
                 &filler_key,
                 &mut Some(&mut filler_stats),
@@ -3286,7 +3336,12 @@ pub mod amm_lp_jit {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 10 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                10 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -2764,6 +2764,7 @@ pub mod fulfill_order {
                 Pubkey::default(),
                 0,
                 100_010_000 * PRICE_PRECISION_U64 / 1_000_000,
+                BASE_PRECISION_U64 / 2,
             )],
             &mut Some(&mut filler),
             &filler_key,
@@ -3196,7 +3197,12 @@ pub mod fulfill_order {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100_010_000 * PRICE_PRECISION_U64 / 1_000_000)],
+            &[(
+                maker_key,
+                0,
+                100_010_000 * PRICE_PRECISION_U64 / 1_000_000,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -3401,7 +3407,12 @@ pub mod fulfill_order {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut None,
             &filler_key,
             &mut None,
@@ -3763,7 +3774,12 @@ pub mod fulfill_order {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 95 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                0,
+                95 * PRICE_PRECISION_U64,
+                2 * BASE_PRECISION_U64,
+            )],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -3949,7 +3965,7 @@ pub mod fulfill_order {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 0, 95 * PRICE_PRECISION_U64)],
+            &[(maker_key, 0, 95 * PRICE_PRECISION_U64, BASE_PRECISION_U64)],
             &mut Some(&mut filler),
             &filler_key,
             &mut Some(&mut filler_stats),
@@ -4660,7 +4676,12 @@ pub mod fulfill_order {
             &mut taker_stats,
             &makers_and_referrers,
             &maker_and_referrer_stats,
-            &[(maker_key, 1, 100 * PRICE_PRECISION_U64)],
+            &[(
+                maker_key,
+                1,
+                100 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64 / 2,
+            )],
             &mut None,
             &filler_key,
             &mut None,
@@ -9415,7 +9436,7 @@ pub mod get_maker_orders_info {
 
         assert_eq!(
             maker_order_price_and_indexes,
-            vec![(maker_key, 1, 100 * PRICE_PRECISION_U64)]
+            vec![(maker_key, 1, 100 * PRICE_PRECISION_U64, BASE_PRECISION_U64)]
         );
     }
 
@@ -9610,7 +9631,7 @@ pub mod get_maker_orders_info {
 
         assert_eq!(
             maker_order_price_and_indexes,
-            vec![(maker_key, 1, 100 * PRICE_PRECISION_U64)]
+            vec![(maker_key, 1, 100 * PRICE_PRECISION_U64, BASE_PRECISION_U64)]
         );
     }
 
@@ -10042,10 +10063,10 @@ pub mod get_maker_orders_info {
         assert_eq!(
             maker_order_price_and_indexes,
             vec![
-                (first_maker_key, 0, 100000000),
-                (second_maker_key, 0, 101000000),
-                (first_maker_key, 1, 102000000),
-                (second_maker_key, 1, 103000000),
+                (first_maker_key, 0, 100000000, BASE_PRECISION_U64),
+                (second_maker_key, 0, 101000000, BASE_PRECISION_U64),
+                (first_maker_key, 1, 102000000, BASE_PRECISION_U64),
+                (second_maker_key, 1, 103000000, BASE_PRECISION_U64),
             ],
         );
     }
@@ -10243,7 +10264,7 @@ pub mod get_maker_orders_info {
 
         assert_eq!(
             maker_order_price_and_indexes,
-            vec![(first_maker_key, 1, 102000000),],
+            vec![(first_maker_key, 1, 102000000, BASE_PRECISION_U64),],
         );
     }
 
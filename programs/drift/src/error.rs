@@ -521,6 +521,14 @@ pub enum ErrorCode {
     CantPayUserInitFee,
     #[msg("CantReclaimRent")]
     CantReclaimRent,
+    #[msg("MaxUsersReached")]
+    MaxUsersReached,
+    #[msg("NameTooLong")]
+    NameTooLong,
+    #[msg("InvalidExpiry")]
+    InvalidExpiry,
+    #[msg("MaxLPSharesMintableExceeded")]
+    MaxLPSharesMintableExceeded,
 }
 
 #[macro_export]
@@ -652,7 +652,9 @@ pub fn handle_initialize_perp_market(
         paused_operations: 0,
         quote_spot_market_index: 0,
         fee_adjustment: 0,
-        padding: [0; 46],
+        padding1: [0; 6],
+        warm_up_start_ts: now,
+        padding: [0; 32],
         amm: AMM {
             oracle: *ctx.accounts.oracle.key,
             oracle_source,
@@ -747,10 +749,13 @@ pub fn handle_initialize_perp_market(
             net_unsettled_funding_pnl: 0,
             quote_asset_amount_with_unsettled_lp: 0,
             reference_price_offset: 0,
-            padding: [0; 12],
+            padding: [0; 4],
+            last_trade_ts_ms: 0,
         },
     };
 
+    perp_market.amm.validate_order_constraints()?;
+
     safe_increment!(state.number_of_markets, 1);
 
     Ok(())
@@ -850,15 +855,7 @@ pub fn handle_update_perp_market_expiry(
 ) -> Result<()> {
     let clock = Clock::get()?;
     let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
-    validate!(
-        clock.unix_timestamp < expiry_ts,
-        ErrorCode::DefaultError,
-        "Market expiry ts must later than current clock timestamp"
-    )?;
-
-    // automatically enter reduce only
-    perp_market.status = MarketStatus::ReduceOnly;
-    perp_market.expiry_ts = expiry_ts;
+    perp_market.set_expiry(expiry_ts, clock.unix_timestamp)?;
 
     Ok(())
 }
@@ -940,6 +937,13 @@ pub fn handle_settle_expired_market_pools_to_revenue_pool(
         "outstanding quote_asset_amounts must be balanced"
     )?;
 
+    validate!(
+        perp_market.can_delist()?,
+        ErrorCode::DefaultError,
+        "market {} has outstanding users, open interest or lp shares",
+        perp_market.market_index
+    )?;
+
     // block when settlement_duration is default/unconfigured
     validate!(
         state.settlement_duration != 0,
@@ -1937,7 +1941,8 @@ pub fn handle_update_perp_market_curve_update_intensity(
         "invalid curve_update_intensity",
     )?;
     let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
-    perp_market.amm.curve_update_intensity = curve_update_intensity;
+    perp_market.amm.curve_update_intensity =
+        curve_update_intensity.min(perp_market.max_curve_update_intensity());
     Ok(())
 }
 
@@ -2258,7 +2263,7 @@ pub fn handle_update_perp_market_max_open_interest(
         "max oi not a multiple of the step size"
     )?;
 
-    perp_market.amm.max_open_interest = max_open_interest;
+    perp_market.set_max_open_interest(max_open_interest)?;
     Ok(())
 }
 
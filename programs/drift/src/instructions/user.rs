@@ -1676,6 +1676,15 @@ pub fn handle_add_perp_lp_shares<'info>(
         )?
         .cast::<u64>()?;
 
+        let max_lp_shares_mintable = market.amm.max_lp_shares_mintable()?;
+        validate!(
+            n_shares.cast::<u128>()? <= max_lp_shares_mintable,
+            ErrorCode::MaxLPSharesMintableExceeded,
+            "minting {} shares exceeds max lp shares mintable {}",
+            n_shares,
+            max_lp_shares_mintable
+        )?;
+
         controller::lp::mint_lp_shares(
             user.force_get_perp_position_mut(market_index)?,
             &mut market,
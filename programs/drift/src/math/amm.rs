@@ -796,9 +796,7 @@ pub fn calculate_amm_available_liquidity(
     amm: &AMM,
     order_direction: &PositionDirection,
 ) -> DriftResult<u64> {
-    let max_fill_size: u64 = (amm.base_asset_reserve / amm.max_fill_reserve_fraction as u128)
-        .min(u64::MAX as u128)
-        .cast()?;
+    let max_fill_size = amm.max_base_per_fill()?;
 
     // one fill can only take up to half of side's liquidity
     let max_base_asset_amount_on_side = match order_direction {
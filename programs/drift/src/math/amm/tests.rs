@@ -443,6 +443,7 @@ fn calc_mark_std_tests() {
     let mut amm = AMM {
         base_asset_reserve: 2 * AMM_RESERVE_PRECISION,
         quote_asset_reserve: 2 * AMM_RESERVE_PRECISION,
+        sqrt_k: 2 * AMM_RESERVE_PRECISION,
         peg_multiplier: PRICE_PRECISION,
         base_spread: 65535, //max base spread is 6.5%
         mark_std: PRICE_PRECISION as u64,
@@ -579,6 +580,7 @@ fn update_mark_twap_tests() {
     let mut amm = AMM {
         quote_asset_reserve: 2 * AMM_RESERVE_PRECISION,
         base_asset_reserve: 2 * AMM_RESERVE_PRECISION,
+        sqrt_k: 2 * AMM_RESERVE_PRECISION,
         peg_multiplier: 40 * PEG_PRECISION,
         base_spread: 0,
         long_spread: 0,
@@ -708,6 +710,7 @@ fn calc_oracle_twap_tests() {
 
     let mut amm = AMM {
         base_asset_reserve: 2 * AMM_RESERVE_PRECISION,
+        sqrt_k: 2 * AMM_RESERVE_PRECISION,
         quote_asset_reserve: 2 * AMM_RESERVE_PRECISION,
         peg_multiplier: PEG_PRECISION,
         historical_oracle_data: HistoricalOracleData {
@@ -797,6 +800,7 @@ fn calc_oracle_twap_clamp_update_tests() {
     let mut amm = AMM {
         quote_asset_reserve: 200 * AMM_RESERVE_PRECISION,
         base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
+        sqrt_k: 200 * AMM_RESERVE_PRECISION,
         peg_multiplier: 13 * PEG_PRECISION,
         base_spread: 0,
         long_spread: 0,
@@ -877,6 +881,7 @@ fn test_last_oracle_conf_update() {
     let mut amm = AMM {
         quote_asset_reserve: 200 * AMM_RESERVE_PRECISION,
         base_asset_reserve: 200 * AMM_RESERVE_PRECISION,
+        sqrt_k: 200 * AMM_RESERVE_PRECISION,
         peg_multiplier: 13 * PEG_PRECISION,
         base_spread: 0,
         long_spread: 0,
@@ -1,10 +1,12 @@
 use crate::controller::position::PositionDirection;
 use crate::error::DriftResult;
 use crate::math::casting::Cast;
-use crate::math::constants::{AMM_RESERVE_PRECISION, PERCENTAGE_PRECISION_U64};
+use crate::math::constants::{
+    AMM_RESERVE_PRECISION, ONE_BPS_DENOMINATOR, PERCENTAGE_PRECISION_U64,
+};
 use crate::math::orders::standardize_base_asset_amount;
 use crate::math::safe_math::SafeMath;
-use crate::state::perp_market::{AMMLiquiditySplit, PerpMarket};
+use crate::state::perp_market::{AMMLiquiditySplit, PerpMarket, AMM};
 #[cfg(test)]
 mod tests;
 
@@ -210,5 +212,91 @@ pub fn calculate_amm_jit_liquidity(
         )?;
     }
 
+    if jit_base_asset_amount > 0 {
+        jit_base_asset_amount = cap_jit_base_asset_amount_for_max_position_size(
+            &market.amm,
+            jit_base_asset_amount,
+            taker_direction,
+        )?;
+    }
+
+    // protocol owned liquidity should only make if it shrinks the amm's inventory
+    if liquidity_split != AMMLiquiditySplit::LPOwned
+        && jit_base_asset_amount > 0
+        && !jit_reduces_inventory(&market.amm, jit_base_asset_amount.cast()?, taker_direction)
+    {
+        jit_base_asset_amount = 0;
+    }
+
     Ok((jit_base_asset_amount, liquidity_split))
 }
+
+/// whether the amm making jit_base against the taker strictly reduces |base_asset_amount_with_amm|
+pub fn jit_reduces_inventory(
+    amm: &AMM,
+    jit_base: i128,
+    taker_direction: PositionDirection,
+) -> bool {
+    let base_asset_amount_with_amm = amm.base_asset_amount_with_amm;
+
+    // taker fill from the amm moves the users' net position in the taker's direction
+    let base_asset_amount_with_amm_after = match taker_direction {
+        PositionDirection::Long => base_asset_amount_with_amm.checked_add(jit_base.abs()),
+        PositionDirection::Short => base_asset_amount_with_amm.checked_sub(jit_base.abs()),
+    };
+
+    match base_asset_amount_with_amm_after {
+        Some(after) => after.unsigned_abs() < base_asset_amount_with_amm.unsigned_abs(),
+        None => false,
+    }
+}
+
+/// caps jit_base so |base_asset_amount_with_amm| stays within amm.max_position_size after the fill.
+/// max_position_size == 0 means no cap
+pub fn cap_jit_base_asset_amount_for_max_position_size(
+    amm: &AMM,
+    jit_base_asset_amount: u64,
+    taker_direction: PositionDirection,
+) -> DriftResult<u64> {
+    if amm.max_position_size == 0 {
+        return Ok(jit_base_asset_amount);
+    }
+
+    let max_position_size = amm.max_position_size.cast::<i128>()?;
+
+    // taker fill from the amm moves the users' net position in the taker's direction
+    let max_jit_base_asset_amount = match taker_direction {
+        PositionDirection::Long => max_position_size.safe_sub(amm.base_asset_amount_with_amm)?,
+        PositionDirection::Short => max_position_size.safe_add(amm.base_asset_amount_with_amm)?,
+    }
+    .max(0)
+    .cast::<u64>()?;
+
+    if jit_base_asset_amount <= max_jit_base_asset_amount {
+        return Ok(jit_base_asset_amount);
+    }
+
+    standardize_base_asset_amount(max_jit_base_asset_amount, amm.order_step_size)
+}
+
+/// bps the taker pays over the amm mid (average of the amm bid and ask) when the jit fill is at
+/// jit_price. negative if the jit fill is better than mid for the taker
+pub fn jit_effective_spread(
+    amm: &AMM,
+    jit_price: u64,
+    reserve_price: u64,
+    direction: PositionDirection,
+) -> DriftResult<i64> {
+    let (bid_price, ask_price) = amm.bid_ask_price(reserve_price)?;
+    let mid_price = bid_price.safe_add(ask_price)?.safe_div(2)?.cast::<i128>()?;
+
+    let price_diff = match direction {
+        PositionDirection::Long => jit_price.cast::<i128>()?.safe_sub(mid_price)?,
+        PositionDirection::Short => mid_price.safe_sub(jit_price.cast()?)?,
+    };
+
+    price_diff
+        .safe_mul(ONE_BPS_DENOMINATOR.cast()?)?
+        .safe_div(mid_price)?
+        .cast()
+}
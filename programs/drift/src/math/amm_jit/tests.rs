@@ -1,4 +1,5 @@
 use crate::math::amm_jit::*;
+use crate::math::constants::PRICE_PRECISION_U64;
 use crate::state::perp_market::AMM;
 
 #[test]
@@ -84,3 +85,97 @@ fn balanced_market_half_intensity() {
     .unwrap();
     assert_eq!(jit_amount, 50);
 }
+
+#[test]
+fn jit_reduces_inventory_checks_magnitude() {
+    // users net short 100, amm is long
+    let amm = AMM {
+        base_asset_amount_with_amm: -100,
+        ..AMM::default_test()
+    };
+
+    assert!(jit_reduces_inventory(&amm, 50, PositionDirection::Long));
+    assert!(jit_reduces_inventory(&amm, 150, PositionDirection::Long));
+    // flips and grows the inventory
+    assert!(!jit_reduces_inventory(&amm, 250, PositionDirection::Long));
+    assert!(!jit_reduces_inventory(&amm, 200, PositionDirection::Long));
+    // wrong side
+    assert!(!jit_reduces_inventory(&amm, 50, PositionDirection::Short));
+    assert!(!jit_reduces_inventory(&amm, 0, PositionDirection::Long));
+
+    let balanced_amm = AMM {
+        base_asset_amount_with_amm: 0,
+        ..AMM::default_test()
+    };
+    assert!(!jit_reduces_inventory(
+        &balanced_amm,
+        50,
+        PositionDirection::Long
+    ));
+}
+
+#[test]
+fn cap_jit_base_asset_amount_for_max_position_size_clamps() {
+    let mut amm = AMM {
+        base_asset_amount_with_amm: -100,
+        max_position_size: 150,
+        ..AMM::default_test()
+    };
+
+    // users net short 100, taker longs 300 would leave them net long 200
+    assert_eq!(
+        cap_jit_base_asset_amount_for_max_position_size(&amm, 300, PositionDirection::Long)
+            .unwrap(),
+        250
+    );
+    assert_eq!(
+        cap_jit_base_asset_amount_for_max_position_size(&amm, 200, PositionDirection::Long)
+            .unwrap(),
+        200
+    );
+    assert_eq!(
+        cap_jit_base_asset_amount_for_max_position_size(&amm, 100, PositionDirection::Short)
+            .unwrap(),
+        50
+    );
+
+    amm.order_step_size = 20;
+    assert_eq!(
+        cap_jit_base_asset_amount_for_max_position_size(&amm, 100, PositionDirection::Short)
+            .unwrap(),
+        40
+    );
+
+    amm.max_position_size = 0;
+    assert_eq!(
+        cap_jit_base_asset_amount_for_max_position_size(&amm, 300, PositionDirection::Long)
+            .unwrap(),
+        300
+    );
+}
+
+#[test]
+fn jit_effective_spread_inside_quoted_spread() {
+    let amm = AMM {
+        long_spread: 10_000,  // 100 bps
+        short_spread: 10_000, // 100 bps
+        ..AMM::default_test()
+    };
+    let reserve_price = 100 * PRICE_PRECISION_U64;
+
+    let ask_price = amm.ask_price(reserve_price).unwrap();
+    let quoted_spread =
+        jit_effective_spread(&amm, ask_price, reserve_price, PositionDirection::Long).unwrap();
+    assert_eq!(quoted_spread, 100);
+
+    // jit fills the taker halfway between mid and the amm ask
+    let jit_price = 100_500_000;
+    let jit_spread =
+        jit_effective_spread(&amm, jit_price, reserve_price, PositionDirection::Long).unwrap();
+    assert_eq!(jit_spread, 50);
+    assert!(jit_spread < quoted_spread);
+
+    let jit_spread =
+        jit_effective_spread(&amm, 99_500_000, reserve_price, PositionDirection::Short).unwrap();
+    assert_eq!(jit_spread, 50);
+}
@@ -11,7 +11,7 @@ use crate::math::constants::{
     AMM_TIMES_PEG_TO_QUOTE_PRECISION_RATIO_I128, AMM_TO_QUOTE_PRECISION_RATIO_I128,
     BID_ASK_SPREAD_PRECISION, BID_ASK_SPREAD_PRECISION_I128, DEFAULT_LARGE_BID_ASK_FACTOR,
     DEFAULT_REVENUE_SINCE_LAST_FUNDING_SPREAD_RETREAT, FUNDING_RATE_BUFFER,
-    MAX_BID_ASK_INVENTORY_SKEW_FACTOR, PEG_PRECISION, PERCENTAGE_PRECISION,
+    MAX_BID_ASK_INVENTORY_SKEW_FACTOR, ONE_BPS_DENOMINATOR, PEG_PRECISION, PERCENTAGE_PRECISION,
     PERCENTAGE_PRECISION_I128, PERCENTAGE_PRECISION_U64, PRICE_PRECISION, PRICE_PRECISION_I128,
     PRICE_PRECISION_I64,
 };
@@ -319,6 +319,19 @@ pub fn calculate_max_target_spread(
     Ok(max_target_spread)
 }
 
+/// smallest bid-ask spread at which an amm fill still pays for the maker rebate. post only
+/// fills against the amm pay the rebate out of the spread the amm captured (total_mm_fee),
+/// so each half of the spread must cover whatever the taker fee doesn't.
+/// precision: BID_ASK_SPREAD_PRECISION
+pub fn calculate_break_even_spread(taker_fee_bps: u32, maker_rebate_bps: u32) -> DriftResult<u32> {
+    let half_spread_bps = maker_rebate_bps.saturating_sub(taker_fee_bps);
+
+    half_spread_bps
+        .safe_mul(2)?
+        .safe_mul(BID_ASK_SPREAD_PRECISION.cast::<u32>()?)?
+        .safe_div(ONE_BPS_DENOMINATOR)
+}
+
 #[allow(clippy::comparison_chain)]
 pub fn calculate_spread(
     base_spread: u32,
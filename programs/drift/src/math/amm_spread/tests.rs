@@ -1509,4 +1509,17 @@ mod test {
         assert_eq!(long_spread, 22137);
         assert_eq!(short_spread, 217356);
     }
+
+    #[test]
+    fn calculate_break_even_spread_tests() {
+        // 2 bps rebate, 1 bps taker fee -> each side needs 1 bps
+        let spread = calculate_break_even_spread(1, 2).unwrap();
+        assert_eq!(spread, 200);
+
+        let no_fee_spread = calculate_break_even_spread(0, 2).unwrap();
+        assert_eq!(no_fee_spread, 400);
+        assert!(no_fee_spread > spread);
+
+        assert_eq!(calculate_break_even_spread(5, 2).unwrap(), 0);
+    }
 }
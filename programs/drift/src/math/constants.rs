@@ -144,6 +144,12 @@ pub const INSURANCE_B_MAX: u64 = ONE_MILLION_QUOTE as u64;
 pub const INSURANCE_C_MAX: u64 = ONE_HUNDRED_THOUSAND_QUOTE as u64;
 pub const INSURANCE_SPECULATIVE_MAX: u64 = 0;
 
+// VOLUME FEE TIERS (24h quote volume)
+pub const VOLUME_FEE_TIER_1_THRESHOLD: u64 = ONE_MILLION_QUOTE;
+pub const VOLUME_FEE_TIER_2_THRESHOLD: u64 = TEN_MILLION_QUOTE;
+pub const VOLUME_FEE_TIER_3_THRESHOLD: u64 = FIFTY_MILLION_QUOTE;
+pub const VOLUME_FEE_TIER_4_THRESHOLD: u64 = ONE_HUNDRED_MILLION_QUOTE;
+
 // QUOTE THRESHOLDS
 pub const FEE_POOL_TO_REVENUE_POOL_THRESHOLD: u128 = TWO_HUNDRED_FIFTY_QUOTE as u128;
 
@@ -176,6 +182,7 @@ pub const MAX_CONCENTRATION_COEFFICIENT: u128 = 1_414_200;
 pub const MAX_LIQUIDATION_SLIPPAGE: i128 = 10_000; // expo = -2
 pub const MAX_LIQUIDATION_SLIPPAGE_U128: u128 = 10_000; // expo = -2
 pub const MAX_MARK_TWAP_DIVERGENCE: u128 = 500_000; // expo = -3
+pub const DEFAULT_SOCIAL_LOSS_STRESS_BPS: u32 = 2000; // 20% adverse move
 
 pub const MAX_MARGIN_RATIO: u32 = MARGIN_PRECISION as u32; // 1x leverage
 pub const MIN_MARGIN_RATIO: u32 = MARGIN_PRECISION as u32 / 50; // 50x leverage
@@ -1,11 +1,17 @@
 use crate::controller::position::PositionDirection;
-use crate::error::DriftResult;
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::amm;
 use crate::math::auction::is_amm_available_liquidity_source;
+use crate::math::casting::Cast;
+use crate::math::constants::ONE_BPS_DENOMINATOR;
 use crate::math::matching::do_orders_cross;
+use crate::math::safe_math::SafeMath;
 use crate::math::safe_unwrap::SafeUnwrap;
 use crate::state::fulfillment::{PerpFulfillmentMethod, SpotFulfillmentMethod};
 use crate::state::perp_market::AMM;
 use crate::state::user::Order;
+use crate::validate;
+use solana_program::msg;
 use solana_program::pubkey::Pubkey;
 
 #[cfg(test)]
@@ -13,15 +19,20 @@ mod tests;
 
 pub fn determine_perp_fulfillment_methods(
     order: &Order,
-    maker_orders_info: &[(Pubkey, usize, u64)],
+    maker_orders_info: &[(Pubkey, usize, u64, u64)],
     amm: &AMM,
     amm_reserve_price: u64,
     valid_oracle_price: Option<i64>,
     limit_price: Option<u64>,
     amm_is_available: bool,
+    now: i64,
     slot: u64,
     min_auction_duration: u8,
 ) -> DriftResult<Vec<PerpFulfillmentMethod>> {
+    if order.is_expired(now) {
+        return Ok(vec![]);
+    }
+
     if order.post_only {
         return determine_perp_fulfillment_methods_for_maker(
             order,
@@ -48,7 +59,7 @@ pub fn determine_perp_fulfillment_methods(
         PositionDirection::Short => amm.ask_price(amm_reserve_price)?,
     };
 
-    for (maker_key, maker_order_index, maker_price) in maker_orders_info.iter() {
+    for (maker_key, maker_order_index, maker_price, _) in maker_orders_info.iter() {
         let taker_crosses_maker = match limit_price {
             Some(taker_price) => do_orders_cross(maker_direction, *maker_price, taker_price),
             None => true,
@@ -94,6 +105,101 @@ pub fn determine_perp_fulfillment_methods(
     Ok(fulfillment_methods)
 }
 
+/// Same as determine_perp_fulfillment_methods, but also returns the taker base the methods can't cover
+pub fn determine_perp_fulfillment_methods_with_unfilled_base(
+    order: &Order,
+    maker_orders_info: &[(Pubkey, usize, u64, u64)],
+    amm: &AMM,
+    amm_reserve_price: u64,
+    valid_oracle_price: Option<i64>,
+    limit_price: Option<u64>,
+    amm_is_available: bool,
+    now: i64,
+    slot: u64,
+    min_auction_duration: u8,
+) -> DriftResult<(Vec<PerpFulfillmentMethod>, u64)> {
+    let fulfillment_methods = determine_perp_fulfillment_methods(
+        order,
+        maker_orders_info,
+        amm,
+        amm_reserve_price,
+        valid_oracle_price,
+        limit_price,
+        amm_is_available,
+        now,
+        slot,
+        min_auction_duration,
+    )?;
+
+    let mut unfilled_base_asset_amount = order.get_base_asset_amount_unfilled(None)?;
+    let mut maker_index = 0_usize;
+    // amm fills walk along the curve, so each amm method only adds depth beyond what's already used
+    let mut amm_base_asset_amount_used = 0_u64;
+    // each amm fill is capped like fulfill_perp_order_with_amm caps it
+    let amm_available_liquidity = amm::calculate_amm_available_liquidity(amm, &order.direction)?;
+
+    for fulfillment_method in fulfillment_methods.iter() {
+        if unfilled_base_asset_amount == 0 {
+            break;
+        }
+
+        let base_asset_amount = match fulfillment_method {
+            PerpFulfillmentMethod::AMM(maker_price) => {
+                let amm_depth = match maker_price.or(limit_price) {
+                    Some(price) => amm.depth_at_prices(&[price], order.direction)?[0],
+                    None => {
+                        let (max_bids, max_asks) = amm::calculate_market_open_bids_asks(amm)?;
+                        match order.direction {
+                            PositionDirection::Long => max_bids.unsigned_abs(),
+                            PositionDirection::Short => max_asks.unsigned_abs(),
+                        }
+                        .min(u64::MAX as u128)
+                        .cast::<u64>()?
+                    }
+                };
+
+                let amm_base_asset_amount = amm_depth
+                    .saturating_sub(amm_base_asset_amount_used)
+                    .min(amm_available_liquidity);
+                amm_base_asset_amount_used =
+                    amm_base_asset_amount_used.safe_add(amm_base_asset_amount)?;
+                amm_base_asset_amount
+            }
+            PerpFulfillmentMethod::Match(_, _) => {
+                let (_, _, _, maker_base_asset_amount) = maker_orders_info[maker_index];
+                maker_index += 1;
+                maker_base_asset_amount
+            }
+        };
+
+        unfilled_base_asset_amount = unfilled_base_asset_amount.saturating_sub(base_asset_amount);
+    }
+
+    Ok((fulfillment_methods, unfilled_base_asset_amount))
+}
+
+/// Total base the makers crossing taker_price can fill, before consulting the amm
+pub fn matchable_base(
+    maker_orders_info: &[(Pubkey, usize, u64, u64)],
+    taker_price: Option<u64>,
+    maker_direction: PositionDirection,
+) -> DriftResult<u64> {
+    let mut matchable_base_asset_amount = 0_u64;
+    for (_, _, maker_price, maker_base_asset_amount) in maker_orders_info.iter() {
+        let taker_crosses_maker = match taker_price {
+            Some(taker_price) => do_orders_cross(maker_direction, *maker_price, taker_price),
+            None => true,
+        };
+
+        if taker_crosses_maker {
+            matchable_base_asset_amount =
+                matchable_base_asset_amount.safe_add(*maker_base_asset_amount)?;
+        }
+    }
+
+    Ok(matchable_base_asset_amount)
+}
+
 fn determine_perp_fulfillment_methods_for_maker(
     order: &Order,
     amm: &AMM,
@@ -130,6 +236,43 @@ fn determine_perp_fulfillment_methods_for_maker(
     }
 }
 
+/// How many bps the maker price improves on the amm price for a taker in `direction`.
+/// Negative if the maker is worse than the amm
+pub fn maker_improvement_bps(
+    maker_price: u64,
+    amm_price: u64,
+    direction: PositionDirection,
+) -> DriftResult<i64> {
+    validate!(
+        amm_price > 0,
+        ErrorCode::DefaultError,
+        "amm_price must be positive"
+    )?;
+
+    let improvement = match direction {
+        PositionDirection::Long => amm_price.cast::<i64>()?.safe_sub(maker_price.cast()?)?,
+        PositionDirection::Short => maker_price.cast::<i64>()?.safe_sub(amm_price.cast()?)?,
+    };
+
+    improvement
+        .cast::<i128>()?
+        .safe_mul(ONE_BPS_DENOMINATOR.cast()?)?
+        .safe_div(amm_price.cast()?)?
+        .cast()
+}
+
+/// The amm quote a taker in `direction` fills at once its auction is complete and it has no limit price
+pub fn auction_complete_amm_price(
+    amm: &AMM,
+    reserve_price: u64,
+    direction: PositionDirection,
+) -> DriftResult<u64> {
+    match direction {
+        PositionDirection::Long => amm.ask_price(reserve_price),
+        PositionDirection::Short => amm.bid_price(reserve_price),
+    }
+}
+
 pub fn determine_spot_fulfillment_methods(
     taker_order: &Order,
     maker_available: bool,
@@ -1,12 +1,13 @@
 mod determine_perp_fulfillment_methods {
     use crate::controller::position::PositionDirection;
     use crate::math::constants::{
-        AMM_RESERVE_PRECISION, PEG_PRECISION, PRICE_PRECISION, PRICE_PRECISION_I64,
-        PRICE_PRECISION_U64,
+        AMM_RESERVE_PRECISION, BASE_PRECISION_U64, PEG_PRECISION, PRICE_PRECISION,
+        PRICE_PRECISION_I64, PRICE_PRECISION_U64,
     };
     use crate::math::fulfillment::determine_perp_fulfillment_methods;
     use crate::state::fulfillment::PerpFulfillmentMethod;
     use crate::state::oracle::HistoricalOracleData;
+    use crate::state::paused_operations::PerpOperation;
     use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
     use crate::state::user::Order;
     use solana_program::pubkey::Pubkey;
@@ -46,6 +47,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -57,7 +59,12 @@ mod determine_perp_fulfillment_methods {
 
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
-            &[(Pubkey::default(), 0, 103 * PRICE_PRECISION_U64)],
+            &[(
+                Pubkey::default(),
+                0,
+                103 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            )],
             &market.amm,
             market.amm.reserve_price().unwrap(),
             Some(oracle_price),
@@ -65,6 +72,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -106,6 +114,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -117,7 +126,12 @@ mod determine_perp_fulfillment_methods {
 
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
-            &[(Pubkey::default(), 0, 99 * PRICE_PRECISION_U64)],
+            &[(
+                Pubkey::default(),
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            )],
             &market.amm,
             market.amm.reserve_price().unwrap(),
             Some(oracle_price),
@@ -125,6 +139,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -172,6 +187,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -189,7 +205,12 @@ mod determine_perp_fulfillment_methods {
 
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
-            &[(Pubkey::default(), 0, 101 * PRICE_PRECISION_U64)],
+            &[(
+                Pubkey::default(),
+                0,
+                101 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            )],
             &market.amm,
             market.amm.reserve_price().unwrap(),
             Some(oracle_price),
@@ -197,6 +218,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -245,6 +267,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -257,8 +280,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 99 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 101 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    101 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -267,6 +300,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -316,6 +350,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -328,7 +363,12 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 99 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
                 (
                     Pubkey::default(),
                     1,
@@ -342,6 +382,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -390,6 +431,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 105 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -402,8 +444,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 102 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 103 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    102 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    103 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -412,6 +464,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -462,6 +515,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Short,
             price: 95 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -474,8 +528,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 101 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 99 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    101 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -484,6 +548,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -533,6 +598,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Short,
             price: 95 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -545,8 +611,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 102 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 101 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    102 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    101 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -555,6 +631,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -603,6 +680,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Short,
             price: 95 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -615,8 +693,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 99 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 98 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    98 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -625,6 +713,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -675,6 +764,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Long,
             price: 99 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -687,8 +777,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 101 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 102 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    101 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    102 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -697,6 +797,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -738,6 +839,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             direction: PositionDirection::Short,
             price: 105 * PRICE_PRECISION_U64,
             ..Order::default()
@@ -750,8 +852,18 @@ mod determine_perp_fulfillment_methods {
         let fulfillment_methods = determine_perp_fulfillment_methods(
             &taker_order,
             &[
-                (Pubkey::default(), 0, 99 * PRICE_PRECISION_U64),
-                (Pubkey::default(), 1, 98 * PRICE_PRECISION_U64),
+                (
+                    Pubkey::default(),
+                    0,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    98 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
             ],
             &market.amm,
             market.amm.reserve_price().unwrap(),
@@ -760,6 +872,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -801,6 +914,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             post_only: true,
             direction: PositionDirection::Long,
             price: 102 * PRICE_PRECISION_U64,
@@ -821,6 +935,7 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
         )
         .unwrap();
 
@@ -862,6 +977,7 @@ mod determine_perp_fulfillment_methods {
         market.amm.min_base_asset_reserve = 0;
 
         let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
             post_only: true,
             direction: PositionDirection::Long,
             price: 99 * PRICE_PRECISION_U64,
@@ -882,9 +998,711 @@ mod determine_perp_fulfillment_methods {
             true,
             0,
             0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(fulfillment_methods, vec![]);
+    }
+
+    #[test]
+    fn expired_taker_order() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            direction: PositionDirection::Long,
+            price: 102 * PRICE_PRECISION_U64,
+            max_ts: 10,
+            ..Order::default()
+        };
+
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let taker_price = Some(taker_order.price);
+
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &[(
+                Pubkey::default(),
+                0,
+                101 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            )],
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(oracle_price),
+            taker_price,
+            true,
+            11,
+            0,
+            0,
         )
         .unwrap();
 
         assert_eq!(fulfillment_methods, vec![]);
+
+        // not yet expired
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &[(
+                Pubkey::default(),
+                0,
+                101 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            )],
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(oracle_price),
+            taker_price,
+            true,
+            10,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert!(!fulfillment_methods.is_empty());
+    }
+
+    #[test]
+    fn amm_covers_remainder_after_small_makers() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            base_asset_amount: 3 * BASE_PRECISION_U64,
+            direction: PositionDirection::Long,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let taker_price = Some(taker_order.price);
+
+        // makers only have 2 of the taker's 3, amm takes the leftover
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &[
+                (
+                    Pubkey::default(),
+                    0,
+                    98 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+            ],
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(oracle_price),
+            taker_price,
+            true,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                PerpFulfillmentMethod::Match(Pubkey::default(), 0),
+                PerpFulfillmentMethod::Match(Pubkey::default(), 1),
+                PerpFulfillmentMethod::AMM(None),
+            ]
+        );
+
+        // makers advertise more than the taker needs, every crossing maker is still kept ahead
+        // of the amm since a maker can fill less than its order size
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &[
+                (
+                    Pubkey::default(),
+                    0,
+                    98 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    99 * PRICE_PRECISION_U64,
+                    2 * BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    2,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+            ],
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(oracle_price),
+            taker_price,
+            true,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                PerpFulfillmentMethod::Match(Pubkey::default(), 0),
+                PerpFulfillmentMethod::Match(Pubkey::default(), 1),
+                PerpFulfillmentMethod::Match(Pubkey::default(), 2),
+                PerpFulfillmentMethod::AMM(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_crossing_makers_when_maker_covers_taker() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            base_asset_amount: 3 * BASE_PRECISION_U64,
+            direction: PositionDirection::Long,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        // the first maker advertises the full taker size, but may fill less (margin, reduce only,
+        // self trade), so the next crossing maker and then the amm take whatever is left
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &[
+                (
+                    Pubkey::default(),
+                    0,
+                    98 * PRICE_PRECISION_U64,
+                    3 * BASE_PRECISION_U64,
+                ),
+                (
+                    Pubkey::default(),
+                    1,
+                    99 * PRICE_PRECISION_U64,
+                    BASE_PRECISION_U64,
+                ),
+            ],
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(100 * PRICE_PRECISION_I64),
+            Some(taker_order.price),
+            true,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [
+                PerpFulfillmentMethod::Match(Pubkey::default(), 0),
+                PerpFulfillmentMethod::Match(Pubkey::default(), 1),
+                PerpFulfillmentMethod::AMM(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn amm_fill_paused() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Active,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            direction: PositionDirection::Long,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let maker_orders_info = [(
+            Pubkey::default(),
+            0,
+            99 * PRICE_PRECISION_U64,
+            BASE_PRECISION_U64,
+        )];
+
+        // same check fill_perp_order does before determining the fulfillment methods
+        let amm_is_available = !market.is_operation_paused(PerpOperation::AmmFill);
+        assert!(amm_is_available);
+
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &maker_orders_info,
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(100 * PRICE_PRECISION_I64),
+            Some(taker_order.price),
+            amm_is_available,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert!(fulfillment_methods.contains(&PerpFulfillmentMethod::AMM(None)));
+
+        market.set_amm_paused(true).unwrap();
+
+        let amm_is_available = !market.is_operation_paused(PerpOperation::AmmFill);
+        assert!(!amm_is_available);
+
+        let fulfillment_methods = determine_perp_fulfillment_methods(
+            &taker_order,
+            &maker_orders_info,
+            &market.amm,
+            market.amm.reserve_price().unwrap(),
+            Some(100 * PRICE_PRECISION_I64),
+            Some(taker_order.price),
+            amm_is_available,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            [PerpFulfillmentMethod::Match(Pubkey::default(), 0)]
+        );
+    }
+}
+
+mod determine_perp_fulfillment_methods_with_unfilled_base {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_U64, PEG_PRECISION, PRICE_PRECISION,
+        PRICE_PRECISION_I64, PRICE_PRECISION_U64,
+    };
+    use crate::math::fulfillment::determine_perp_fulfillment_methods_with_unfilled_base;
+    use crate::state::fulfillment::PerpFulfillmentMethod;
+    use crate::state::oracle::HistoricalOracleData;
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+    use crate::state::user::Order;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn large_taker_partially_covered() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let taker_price = Some(taker_order.price);
+
+        let (fulfillment_methods, unfilled_base_asset_amount) =
+            determine_perp_fulfillment_methods_with_unfilled_base(
+                &taker_order,
+                &[(
+                    Pubkey::default(),
+                    0,
+                    101 * PRICE_PRECISION_U64,
+                    2 * BASE_PRECISION_U64,
+                )],
+                &market.amm,
+                market.amm.reserve_price().unwrap(),
+                Some(oracle_price),
+                taker_price,
+                true,
+                0,
+                0,
+                0,
+            )
+            .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            vec![
+                PerpFulfillmentMethod::AMM(Some(101 * PRICE_PRECISION_U64)),
+                PerpFulfillmentMethod::Match(Pubkey::default(), 0),
+                PerpFulfillmentMethod::AMM(None)
+            ]
+        );
+
+        // amm covers up to the taker limit price, maker covers 2
+        let amm_depth = market
+            .amm
+            .depth_at_prices(&[102 * PRICE_PRECISION_U64], PositionDirection::Long)
+            .unwrap()[0];
+        assert!(amm_depth > 0);
+        assert_eq!(
+            unfilled_base_asset_amount,
+            10 * BASE_PRECISION_U64 - 2 * BASE_PRECISION_U64 - amm_depth
+        );
+    }
+
+    #[test]
+    fn per_fill_cap_leaves_remainder() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                bid_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                ask_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 50,
+                // one fill can take at most 100 / 1000 = 0.1 base
+                max_fill_reserve_fraction: 1000,
+                order_step_size: 10000000,
+                order_tick_size: 1,
+                base_spread: 100,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap: (100 * PRICE_PRECISION) as i64,
+                    last_oracle_price_twap_5min: (100 * PRICE_PRECISION) as i64,
+
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default_test()
+        };
+        market.amm.max_base_asset_reserve = u128::MAX;
+        market.amm.min_base_asset_reserve = 0;
+
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let (fulfillment_methods, unfilled_base_asset_amount) =
+            determine_perp_fulfillment_methods_with_unfilled_base(
+                &taker_order,
+                &[],
+                &market.amm,
+                market.amm.reserve_price().unwrap(),
+                Some(100 * PRICE_PRECISION_I64),
+                Some(taker_order.price),
+                true,
+                0,
+                0,
+                0,
+            )
+            .unwrap();
+
+        assert_eq!(fulfillment_methods, vec![PerpFulfillmentMethod::AMM(None)]);
+
+        // the curve has ~0.98 base up to $102, but the fill is capped at 0.1
+        assert_eq!(unfilled_base_asset_amount, 9_900_000_000);
+    }
+
+    #[test]
+    fn fully_covered() {
+        let market = PerpMarket::default_test();
+
+        let taker_order = Order {
+            direction: PositionDirection::Long,
+            base_asset_amount: BASE_PRECISION_U64,
+            price: 102 * PRICE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let (fulfillment_methods, unfilled_base_asset_amount) =
+            determine_perp_fulfillment_methods_with_unfilled_base(
+                &taker_order,
+                &[(
+                    Pubkey::default(),
+                    0,
+                    101 * PRICE_PRECISION_U64,
+                    2 * BASE_PRECISION_U64,
+                )],
+                &market.amm,
+                market.amm.reserve_price().unwrap(),
+                Some(100 * PRICE_PRECISION_I64),
+                Some(taker_order.price),
+                false,
+                0,
+                0,
+                0,
+            )
+            .unwrap();
+
+        assert_eq!(
+            fulfillment_methods,
+            vec![PerpFulfillmentMethod::Match(Pubkey::default(), 0)]
+        );
+        assert_eq!(unfilled_base_asset_amount, 0);
+    }
+}
+
+mod matchable_base {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{BASE_PRECISION_U64, PRICE_PRECISION_U64};
+    use crate::math::fulfillment::matchable_base;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn skips_non_crossing_maker() {
+        // taker buying up to $100, makers asking $99, $100 and $101
+        let maker_orders_info = [
+            (
+                Pubkey::new_unique(),
+                0,
+                99 * PRICE_PRECISION_U64,
+                BASE_PRECISION_U64,
+            ),
+            (
+                Pubkey::new_unique(),
+                1,
+                100 * PRICE_PRECISION_U64,
+                2 * BASE_PRECISION_U64,
+            ),
+            (
+                Pubkey::new_unique(),
+                2,
+                101 * PRICE_PRECISION_U64,
+                4 * BASE_PRECISION_U64,
+            ),
+        ];
+
+        let base = matchable_base(
+            &maker_orders_info,
+            Some(100 * PRICE_PRECISION_U64),
+            PositionDirection::Short,
+        )
+        .unwrap();
+        assert_eq!(base, 3 * BASE_PRECISION_U64);
+
+        // no limit price crosses every maker
+        let base = matchable_base(&maker_orders_info, None, PositionDirection::Short).unwrap();
+        assert_eq!(base, 7 * BASE_PRECISION_U64);
+    }
+}
+
+mod maker_improvement_bps {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::PRICE_PRECISION_U64;
+    use crate::math::fulfillment::maker_improvement_bps;
+
+    #[test]
+    fn maker_inside_amm() {
+        let amm_price = 100 * PRICE_PRECISION_U64;
+
+        // taker buying, maker asks 5 bps below amm ask
+        let maker_price = amm_price - amm_price / 2000;
+        assert_eq!(
+            maker_improvement_bps(maker_price, amm_price, PositionDirection::Long).unwrap(),
+            5
+        );
+
+        // taker selling, maker bids 5 bps above amm bid
+        let maker_price = amm_price + amm_price / 2000;
+        assert_eq!(
+            maker_improvement_bps(maker_price, amm_price, PositionDirection::Short).unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn maker_outside_amm() {
+        let amm_price = 100 * PRICE_PRECISION_U64;
+        let maker_price = amm_price + amm_price / 1000;
+
+        assert_eq!(
+            maker_improvement_bps(maker_price, amm_price, PositionDirection::Long).unwrap(),
+            -10
+        );
+    }
+}
+
+mod auction_complete_amm_price {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::PRICE_PRECISION_U64;
+    use crate::math::fulfillment::auction_complete_amm_price;
+    use crate::state::perp_market::AMM;
+
+    #[test]
+    fn taker_direction() {
+        let amm = AMM {
+            long_spread: 1000,
+            short_spread: 2000,
+            ..AMM::default()
+        };
+        let reserve_price = 100 * PRICE_PRECISION_U64;
+
+        // taker buying lifts the ask
+        assert_eq!(
+            auction_complete_amm_price(&amm, reserve_price, PositionDirection::Long).unwrap(),
+            100_100_000
+        );
+
+        // taker selling hits the bid
+        assert_eq!(
+            auction_complete_amm_price(&amm, reserve_price, PositionDirection::Short).unwrap(),
+            99_800_000
+        );
     }
 }
@@ -1,14 +1,16 @@
 use crate::controller::funding::update_funding_rate;
 use crate::math::constants::{
-    AMM_RESERVE_PRECISION, ONE_HOUR_I128, PRICE_PRECISION, PRICE_PRECISION_U64, QUOTE_PRECISION,
+    AMM_RESERVE_PRECISION, ONE_HOUR_I128, PEG_PRECISION, PRICE_PRECISION, PRICE_PRECISION_U64,
+    QUOTE_PRECISION,
 };
 use crate::math::funding::*;
+use crate::math::oracle::block_operation;
 use std::cmp::min;
 
 use crate::test_utils::get_pyth_price;
 
 // use crate::create_anchor_account_info;
-use crate::state::oracle::HistoricalOracleData;
+use crate::state::oracle::{HistoricalOracleData, OraclePriceData};
 use crate::state::oracle_map::OracleMap;
 use crate::state::perp_market::{ContractTier, PerpMarket, AMM};
 use crate::state::state::{OracleGuardRails, State, ValidityGuardRails};
@@ -573,3 +575,83 @@ fn max_funding_rates() {
 
     assert!(!did_succeed);
 }
+
+#[test]
+fn invalid_oracle_blocks_funding_update() {
+    let now = 3600_i64;
+    let slot = 0_u64;
+
+    let state = State {
+        oracle_guard_rails: OracleGuardRails::default(),
+        ..State::default()
+    };
+
+    // confidence is the whole price, so the oracle is too uncertain to update funding with
+    let mut oracle_price = get_pyth_price(50, 6);
+    oracle_price.agg.conf = oracle_price.agg.price as u64;
+    let oracle_price_key =
+        Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+    let pyth_program = crate::ids::pyth_program::id();
+    create_account_info!(
+        oracle_price,
+        &oracle_price_key,
+        &pyth_program,
+        oracle_account_info
+    );
+    let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+    let mut market = PerpMarket {
+        market_index: 0,
+        amm: AMM {
+            oracle: oracle_price_key,
+            base_asset_reserve: 500 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 500 * AMM_RESERVE_PRECISION,
+            sqrt_k: 500 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 50 * PEG_PRECISION,
+            last_mark_price_twap: 50 * PRICE_PRECISION_U64,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price: (50 * PRICE_PRECISION) as i64,
+                last_oracle_price_twap: (50 * PRICE_PRECISION) as i64,
+                last_oracle_price_twap_5min: (50 * PRICE_PRECISION) as i64,
+                ..HistoricalOracleData::default()
+            },
+            funding_period: 3600,
+            ..AMM::default()
+        },
+        ..PerpMarket::default()
+    };
+
+    // the funding period has elapsed and the amm is fresh, so only the oracle holds funding back
+    let valid_oracle_price_data = OraclePriceData {
+        price: (50 * PRICE_PRECISION) as i64,
+        confidence: 0,
+        delay: 0,
+        has_sufficient_number_of_data_points: true,
+    };
+    assert!(!block_operation(
+        &market,
+        &valid_oracle_price_data,
+        &state.oracle_guard_rails,
+        None,
+        slot
+    )
+    .unwrap());
+
+    let did_succeed = update_funding_rate(
+        0,
+        &mut market,
+        &mut oracle_map,
+        now,
+        slot,
+        &state.oracle_guard_rails,
+        false,
+        None,
+    )
+    .unwrap();
+
+    assert!(!did_succeed);
+    assert_eq!(market.amm.last_funding_rate_ts, 0);
+    assert_eq!(market.amm.last_funding_rate, 0);
+    assert_eq!(market.amm.cumulative_funding_rate_long, 0);
+    assert_eq!(market.amm.cumulative_funding_rate_short, 0);
+}
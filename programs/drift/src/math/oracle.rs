@@ -3,7 +3,7 @@ use std::cmp::max;
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::msg;
 
-use crate::error::DriftResult;
+use crate::error::{DriftResult, ErrorCode};
 use crate::math::amm;
 use crate::math::casting::Cast;
 use crate::math::constants::BID_ASK_SPREAD_PRECISION;
@@ -11,9 +11,10 @@ use crate::math::safe_math::SafeMath;
 
 use crate::state::oracle::OraclePriceData;
 use crate::state::paused_operations::PerpOperation;
-use crate::state::perp_market::PerpMarket;
+use crate::state::perp_market::{PerpMarket, AMM};
 use crate::state::state::{OracleGuardRails, ValidityGuardRails};
 use crate::state::user::MarketType;
+use crate::validate;
 use std::fmt;
 
 #[cfg(test)]
@@ -297,3 +298,66 @@ pub fn oracle_validity(
 
     Ok(oracle_validity)
 }
+
+/// Validity of the oracle for an amm, using the base confidence interval (no contract tier scaling)
+pub fn oracle_validity_reason(
+    amm: &AMM,
+    oracle_price_data: &OraclePriceData,
+    guard_rails: &OracleGuardRails,
+) -> DriftResult<OracleValidity> {
+    // oracle_validity errors computing the conf pct for non-positive prices
+    if oracle_price_data.price <= 0 {
+        return Ok(OracleValidity::Invalid);
+    }
+
+    oracle_validity(
+        MarketType::Perp,
+        0,
+        amm.historical_oracle_data.last_oracle_price_twap,
+        oracle_price_data,
+        &guard_rails.validity,
+        1,
+        false,
+    )
+}
+
+/// Interpolates between the fresh oracle price and the last valid price by the oracle's delay.
+/// No delay gives the fresh price, a delay of max_delay or more gives the last valid price
+pub fn blended_oracle_price(
+    oracle_price_data: &OraclePriceData,
+    last_valid_price: i64,
+    max_delay: i64,
+) -> DriftResult<i64> {
+    validate!(
+        max_delay > 0,
+        ErrorCode::DefaultError,
+        "max_delay={} must be positive",
+        max_delay
+    )?;
+
+    let delay = oracle_price_data.delay.clamp(0, max_delay);
+
+    oracle_price_data
+        .price
+        .cast::<i128>()?
+        .safe_mul(max_delay.safe_sub(delay)?.cast()?)?
+        .safe_add(last_valid_price.cast::<i128>()?.safe_mul(delay.cast()?)?)?
+        .safe_div(max_delay.cast()?)?
+        .cast()
+}
+
+/// mark_price - oracle price in PRICE_PRECISION, the absolute counterpart to
+/// amm::calculate_oracle_reserve_price_spread_pct
+pub fn oracle_mark_spread_abs(
+    amm: &AMM,
+    oracle_price_data: &OraclePriceData,
+    mark_price: u128,
+) -> DriftResult<i128> {
+    let (_oracle_price, price_spread) = amm::calculate_oracle_reserve_price_spread(
+        amm,
+        oracle_price_data,
+        Some(mark_price.cast()?),
+    )?;
+
+    price_spread.cast()
+}
@@ -1,6 +1,6 @@
 use crate::math::amm::update_oracle_price_twap;
 use crate::math::constants::{
-    AMM_RESERVE_PRECISION, PEG_PRECISION, PRICE_PRECISION, PRICE_PRECISION_U64,
+    AMM_RESERVE_PRECISION, PEG_PRECISION, PRICE_PRECISION, PRICE_PRECISION_I64, PRICE_PRECISION_U64,
 };
 use crate::math::oracle::*;
 use crate::state::oracle::HistoricalOracleData;
@@ -15,6 +15,7 @@ fn calculate_oracle_valid() {
     let px = 32 * PRICE_PRECISION;
     let amm = AMM {
         base_asset_reserve: 2 * AMM_RESERVE_PRECISION,
+        sqrt_k: 2 * AMM_RESERVE_PRECISION,
         quote_asset_reserve: 2 * AMM_RESERVE_PRECISION,
         peg_multiplier: 33 * PEG_PRECISION,
         historical_oracle_data: HistoricalOracleData {
@@ -106,3 +107,146 @@ fn calculate_oracle_valid() {
     assert!(oracle_status.mark_too_divergent);
     assert!(oracle_status.oracle_validity == OracleValidity::TooUncertain);
 }
+
+#[test]
+fn oracle_validity_reason_mapping() {
+    let amm = AMM {
+        historical_oracle_data: HistoricalOracleData {
+            last_oracle_price_twap: (34 * PRICE_PRECISION) as i64,
+            ..HistoricalOracleData::default()
+        },
+        ..AMM::default()
+    };
+    let guard_rails = OracleGuardRails {
+        validity: ValidityGuardRails {
+            slots_before_stale_for_amm: 10,
+            slots_before_stale_for_margin: 120,
+            confidence_interval_max_size: 20000, // 2%
+            too_volatile_ratio: 5,
+        },
+        ..OracleGuardRails::default()
+    };
+    let valid_oracle_price_data = OraclePriceData {
+        price: (34 * PRICE_PRECISION) as i64,
+        confidence: PRICE_PRECISION_U64 / 100,
+        delay: 1,
+        has_sufficient_number_of_data_points: true,
+    };
+
+    let reason = |oracle_price_data: OraclePriceData| {
+        oracle_validity_reason(&amm, &oracle_price_data, &guard_rails).unwrap()
+    };
+
+    assert_eq!(reason(valid_oracle_price_data), OracleValidity::Valid);
+
+    assert_eq!(
+        reason(OraclePriceData {
+            price: 0,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::Invalid
+    );
+    assert_eq!(
+        reason(OraclePriceData {
+            price: -1,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::Invalid
+    );
+
+    assert_eq!(
+        reason(OraclePriceData {
+            price: (340 * PRICE_PRECISION) as i64,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::TooVolatile
+    );
+
+    assert_eq!(
+        reason(OraclePriceData {
+            confidence: PRICE_PRECISION_U64,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::TooUncertain
+    );
+
+    assert_eq!(
+        reason(OraclePriceData {
+            delay: 121,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::StaleForMargin
+    );
+
+    assert_eq!(
+        reason(OraclePriceData {
+            has_sufficient_number_of_data_points: false,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::InsufficientDataPoints
+    );
+
+    assert_eq!(
+        reason(OraclePriceData {
+            delay: 11,
+            ..valid_oracle_price_data
+        }),
+        OracleValidity::StaleForAMM
+    );
+}
+
+#[test]
+fn blended_oracle_price_by_delay() {
+    let last_valid_price = 100 * PRICE_PRECISION_I64;
+    let mut oracle_price_data = OraclePriceData {
+        price: 110 * PRICE_PRECISION_I64,
+        confidence: 0,
+        delay: 0,
+        has_sufficient_number_of_data_points: true,
+    };
+    let max_delay = 20;
+
+    assert_eq!(
+        blended_oracle_price(&oracle_price_data, last_valid_price, max_delay).unwrap(),
+        110 * PRICE_PRECISION_I64
+    );
+
+    oracle_price_data.delay = 10;
+    assert_eq!(
+        blended_oracle_price(&oracle_price_data, last_valid_price, max_delay).unwrap(),
+        105 * PRICE_PRECISION_I64
+    );
+
+    oracle_price_data.delay = max_delay;
+    assert_eq!(
+        blended_oracle_price(&oracle_price_data, last_valid_price, max_delay).unwrap(),
+        last_valid_price
+    );
+
+    oracle_price_data.delay = 2 * max_delay;
+    assert_eq!(
+        blended_oracle_price(&oracle_price_data, last_valid_price, max_delay).unwrap(),
+        last_valid_price
+    );
+}
+
+#[test]
+fn oracle_mark_spread_abs_signed() {
+    let amm = AMM::default();
+    let oracle_price_data = OraclePriceData {
+        price: 100 * PRICE_PRECISION_I64,
+        confidence: 0,
+        delay: 0,
+        has_sufficient_number_of_data_points: true,
+    };
+
+    assert_eq!(
+        oracle_mark_spread_abs(&amm, &oracle_price_data, 110 * PRICE_PRECISION).unwrap(),
+        10 * PRICE_PRECISION as i128
+    );
+
+    assert_eq!(
+        oracle_mark_spread_abs(&amm, &oracle_price_data, 90 * PRICE_PRECISION).unwrap(),
+        -10 * PRICE_PRECISION as i128
+    );
+}
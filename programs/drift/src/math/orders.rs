@@ -364,7 +364,7 @@ pub fn should_expire_order(user: &User, user_order_index: usize, now: i64) -> Dr
         return Ok(false);
     }
 
-    Ok(now > order.max_ts)
+    Ok(order.is_expired(now))
 }
 
 pub fn should_cancel_reduce_only_order(
@@ -3234,6 +3234,7 @@ pub mod get_price_for_perp_order {
     fn bid_crosses_vamm_ask() {
         let amm = AMM {
             base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
             quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
             peg_multiplier: 100 * PEG_PRECISION,
             order_tick_size: 100000,
@@ -3265,6 +3266,7 @@ pub mod get_price_for_perp_order {
     fn bid_doesnt_cross_vamm_ask() {
         let amm = AMM {
             base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
             quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
             peg_multiplier: 100 * PEG_PRECISION,
             order_tick_size: 100000,
@@ -3290,6 +3292,7 @@ pub mod get_price_for_perp_order {
     fn ask_crosses_vamm_ask() {
         let amm = AMM {
             base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
             quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
             peg_multiplier: 100 * PEG_PRECISION,
             order_tick_size: 100000,
@@ -3321,6 +3324,7 @@ pub mod get_price_for_perp_order {
     fn ask_doesnt_cross_vamm_ask() {
         let amm = AMM {
             base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
             quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
             peg_multiplier: 100 * PEG_PRECISION,
             order_tick_size: 100000,
@@ -3,6 +3,9 @@ use crate::math::casting::Cast;
 use crate::math::safe_math::SafeMath;
 use std::cmp::max;
 
+#[cfg(test)]
+mod tests;
+
 pub fn calculate_rolling_sum(
     data1: u64,
     data2: u64,
@@ -18,6 +21,21 @@ pub fn calculate_rolling_sum(
     prev_twap_99.cast::<u64>()?.safe_add(data2)
 }
 
+pub fn calculate_rolling_sum_i64(
+    last_sum: i64,
+    new_value: i64,
+    since_last: i128,
+    window: i128,
+) -> DriftResult<i64> {
+    // signed version of calculate_rolling_sum for funding/pnl
+    let prev_sum_decayed = last_sum
+        .cast::<i128>()?
+        .safe_mul(max(0, window.safe_sub(since_last)?))?
+        .safe_div(window)?;
+
+    prev_sum_decayed.cast::<i64>()?.safe_add(new_value)
+}
+
 pub fn calculate_weighted_average(
     data1: i64,
     data2: i64,
@@ -0,0 +1,20 @@
+mod calculate_rolling_sum_i64 {
+    use crate::math::constants::ONE_HOUR_I128;
+    use crate::math::stats::calculate_rolling_sum_i64;
+
+    #[test]
+    fn negative_value_decays_positive_sum() {
+        let window = 24 * ONE_HOUR_I128;
+
+        // half the window elapsed
+        let sum = calculate_rolling_sum_i64(1000, -300, 12 * ONE_HOUR_I128, window).unwrap();
+        assert_eq!(sum, 200);
+
+        let sum = calculate_rolling_sum_i64(1000, -1500, 12 * ONE_HOUR_I128, window).unwrap();
+        assert_eq!(sum, -1000);
+
+        // window fully elapsed drops the previous sum
+        let sum = calculate_rolling_sum_i64(1000, -300, 2 * window, window).unwrap();
+        assert_eq!(sum, -300);
+    }
+}
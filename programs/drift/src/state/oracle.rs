@@ -166,14 +166,36 @@ pub fn get_pyth_price(
     price_oracle: &AccountInfo,
     clock_slot: u64,
     multiple: u128,
+) -> DriftResult<OraclePriceData> {
+    get_pyth_price_component(price_oracle, clock_slot, multiple, false)
+}
+
+/// same as get_pyth_price but reads pyth's ema price and confidence instead of the aggregate,
+/// which is smoother for funding
+pub fn get_pyth_price_ema(
+    price_oracle: &AccountInfo,
+    clock_slot: u64,
+    multiple: u128,
+) -> DriftResult<OraclePriceData> {
+    get_pyth_price_component(price_oracle, clock_slot, multiple, true)
+}
+
+fn get_pyth_price_component(
+    price_oracle: &AccountInfo,
+    clock_slot: u64,
+    multiple: u128,
+    use_ema: bool,
 ) -> DriftResult<OraclePriceData> {
     let pyth_price_data = price_oracle
         .try_borrow_data()
         .or(Err(crate::error::ErrorCode::UnableToLoadOracle))?;
     let price_data = pyth_client::cast::<pyth_client::Price>(&pyth_price_data);
 
-    let oracle_price = price_data.agg.price;
-    let oracle_conf = price_data.agg.conf;
+    let (oracle_price, oracle_conf) = if use_ema {
+        (price_data.twap.val, price_data.twac.val.cast::<u64>()?)
+    } else {
+        (price_data.agg.price, price_data.agg.conf)
+    };
 
     let min_publishers = price_data.num.min(3);
     let publisher_count = price_data.num_qt;
@@ -309,7 +331,8 @@ pub fn get_prelaunch_price(price_oracle: &AccountInfo, slot: u64) -> DriftResult
     Ok(OraclePriceData {
         price: oracle.price,
         confidence: oracle.confidence,
-        delay: oracle.amm_last_update_slot.saturating_sub(slot).cast()?,
+        // staleness is measured from the last oracle update, same as the other sources
+        delay: slot.saturating_sub(oracle.last_update_slot).cast()?,
         has_sufficient_number_of_data_points: true,
     })
 }
@@ -1,11 +1,15 @@
 use std::str::FromStr;
 
+use anchor_lang::Owner;
 use solana_program::pubkey::Pubkey;
 
-use crate::create_account_info;
-use crate::state::oracle::{get_oracle_price, OracleSource};
+use crate::state::oracle::{
+    get_oracle_price, get_pyth_price, get_pyth_price_ema, OracleSource, PrelaunchOracle,
+};
 use crate::state::perp_market::AMM;
 use crate::test_utils::*;
+use crate::{create_account_info, create_anchor_account_info};
+use crate::{PRICE_PRECISION_I64, PRICE_PRECISION_U64};
 
 #[test]
 fn pyth_1k() {
@@ -58,3 +62,52 @@ fn pyth_1m() {
     let twap = amm.get_oracle_twap(&oracle_account_info, 0).unwrap();
     assert_eq!(twap, Some(839400));
 }
+
+#[test]
+fn pyth_agg_vs_ema() {
+    let mut oracle_price = get_hardcoded_pyth_price(100 * PRICE_PRECISION_I64, 6);
+    oracle_price.twap = 99 * PRICE_PRECISION_I64;
+    let oracle_price_key =
+        Pubkey::from_str("8ihFLu5FimgTQ1Unh4dVyEHUGodJ5gJQCrQf4KUVB9bN").unwrap();
+    let pyth_program = crate::ids::pyth_program::id();
+    create_account_info!(
+        oracle_price,
+        &oracle_price_key,
+        &pyth_program,
+        oracle_account_info
+    );
+
+    let agg_price_data = get_pyth_price(&oracle_account_info, 0, 1).unwrap();
+    assert_eq!(agg_price_data.price, 100 * PRICE_PRECISION_I64);
+
+    let ema_price_data = get_pyth_price_ema(&oracle_account_info, 0, 1).unwrap();
+    assert_eq!(ema_price_data.price, 99 * PRICE_PRECISION_I64);
+    assert_eq!(ema_price_data.delay, agg_price_data.delay);
+}
+
+#[test]
+fn prelaunch() {
+    let mut oracle = PrelaunchOracle {
+        price: 32 * PRICE_PRECISION_I64,
+        max_price: 100 * PRICE_PRECISION_I64,
+        confidence: PRICE_PRECISION_U64 / 10,
+        last_update_slot: 100,
+        amm_last_update_slot: 95,
+        ..PrelaunchOracle::default()
+    };
+    create_anchor_account_info!(oracle, PrelaunchOracle, oracle_account_info);
+
+    let oracle_price_data =
+        get_oracle_price(&OracleSource::Prelaunch, &oracle_account_info, 110).unwrap();
+    assert_eq!(oracle_price_data.price, 32 * PRICE_PRECISION_I64);
+    assert_eq!(oracle_price_data.confidence, PRICE_PRECISION_U64 / 10);
+    assert_eq!(oracle_price_data.delay, 10);
+
+    let amm = AMM {
+        oracle_source: OracleSource::Prelaunch,
+        ..AMM::default()
+    };
+
+    let twap = amm.get_oracle_twap(&oracle_account_info, 110).unwrap();
+    assert_eq!(twap, Some(32 * PRICE_PRECISION_I64));
+}
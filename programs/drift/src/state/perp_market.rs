@@ -2,9 +2,13 @@ use anchor_lang::prelude::*;
 
 use std::cmp::max;
 
+use crate::controller::amm::SwapDirection;
 use crate::controller::position::{PositionDelta, PositionDirection};
+use crate::controller::spot_balance::update_spot_balances;
 use crate::error::{DriftResult, ErrorCode};
 use crate::math::amm;
+use crate::math::amm_spread;
+use crate::math::bn;
 use crate::math::casting::Cast;
 #[cfg(test)]
 use crate::math::constants::{
@@ -12,29 +16,39 @@ use crate::math::constants::{
 };
 use crate::math::constants::{
     AMM_RESERVE_PRECISION_I128, AMM_TO_QUOTE_PRECISION_RATIO, BID_ASK_SPREAD_PRECISION,
-    BID_ASK_SPREAD_PRECISION_U128, DEFAULT_REVENUE_SINCE_LAST_FUNDING_SPREAD_RETREAT,
-    LP_FEE_SLICE_DENOMINATOR, LP_FEE_SLICE_NUMERATOR, MARGIN_PRECISION_U128, PERCENTAGE_PRECISION,
+    BID_ASK_SPREAD_PRECISION_I128, BID_ASK_SPREAD_PRECISION_U128,
+    DEFAULT_REVENUE_SINCE_LAST_FUNDING_SPREAD_RETREAT, DEFAULT_SOCIAL_LOSS_STRESS_BPS, FIVE_MINUTE,
+    FUNDING_RATE_BUFFER_I128, LP_FEE_SLICE_DENOMINATOR, LP_FEE_SLICE_NUMERATOR,
+    MARGIN_PRECISION_U128, ONE_BPS_DENOMINATOR, ONE_YEAR, PERCENTAGE_PRECISION,
     PERCENTAGE_PRECISION_I128, PERCENTAGE_PRECISION_I64, PERCENTAGE_PRECISION_U64, PRICE_PRECISION,
-    SPOT_WEIGHT_PRECISION, TWENTY_FOUR_HOUR,
+    PRICE_PRECISION_I128, PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO,
+    PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128, PRICE_TO_PEG_PRECISION_RATIO,
+    SPOT_RATE_PRECISION, SPOT_WEIGHT_PRECISION, TWENTY_FOUR_HOUR, VOLUME_FEE_TIER_1_THRESHOLD,
+    VOLUME_FEE_TIER_2_THRESHOLD, VOLUME_FEE_TIER_3_THRESHOLD, VOLUME_FEE_TIER_4_THRESHOLD,
 };
+use crate::math::cp_curve;
+use crate::math::funding::calculate_funding_payment_in_quote_precision;
 use crate::math::helpers::get_proportion_i128;
 
 use crate::math::margin::{
     calculate_size_discount_asset_weight, calculate_size_premium_liability_weight,
     MarginRequirementType,
 };
+use crate::math::orders::is_multiple_of_step_size;
 use crate::math::safe_math::SafeMath;
+use crate::math::spot_balance::get_token_amount;
 use crate::math::stats;
 use crate::state::events::OrderActionExplanation;
 
 use crate::state::oracle::{
-    get_prelaunch_price, get_switchboard_price, HistoricalOracleData, OracleSource,
+    get_prelaunch_price, get_switchboard_price, HistoricalOracleData, OraclePriceData, OracleSource,
 };
-use crate::state::spot_market::{AssetTier, SpotBalance, SpotBalanceType};
+use crate::state::spot_market::{AssetTier, SpotBalance, SpotBalanceType, SpotMarket};
 use crate::state::traits::{MarketIndexOffset, Size};
 use borsh::{BorshDeserialize, BorshSerialize};
 
 use crate::state::paused_operations::PerpOperation;
+use crate::validate;
 use drift_macros::assert_no_slop;
 use static_assertions::const_assert_eq;
 
@@ -84,6 +98,22 @@ impl MarketStatus {
             Ok(())
         }
     }
+
+    pub fn can_transition_to(&self, new_status: MarketStatus) -> bool {
+        match new_status {
+            MarketStatus::Active => {
+                matches!(self, MarketStatus::Initialized | MarketStatus::ReduceOnly)
+            }
+            MarketStatus::ReduceOnly => {
+                matches!(self, MarketStatus::Initialized | MarketStatus::Active)
+            }
+            MarketStatus::Settlement => {
+                matches!(self, MarketStatus::Active | MarketStatus::ReduceOnly)
+            }
+            MarketStatus::Delisted => *self == MarketStatus::Settlement,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
@@ -230,7 +260,10 @@ pub struct PerpMarket {
     /// E.g. if this is -50 and the fee is 5bps, the new fee will be 2.5bps
     /// if this is 50 and the fee is 5bps, the new fee will be 7.5bps
     pub fee_adjustment: i16,
-    pub padding: [u8; 46],
+    pub padding1: [u8; 6],
+    /// The ts when the market was initialized, starting its warm up period
+    pub warm_up_start_ts: i64,
+    pub padding: [u8; 32],
 }
 
 impl Default for PerpMarket {
@@ -264,7 +297,9 @@ impl Default for PerpMarket {
             paused_operations: 0,
             quote_spot_market_index: 0,
             fee_adjustment: 0,
-            padding: [0; 46],
+            padding1: [0; 6],
+            warm_up_start_ts: 0,
+            padding: [0; 32],
         }
     }
 }
@@ -287,14 +322,162 @@ impl PerpMarket {
         in_settlement || expired
     }
 
+    pub fn requires_settlement_at_expiry(&self) -> bool {
+        self.contract_type == ContractType::Future
+    }
+
+    /// annualized basis between the reserve price and the oracle over the time left to expiry.
+    /// precision: PERCENTAGE_PRECISION
+    pub fn implied_basis_apr(&self, now: i64, oracle_price: i64) -> DriftResult<i64> {
+        validate!(
+            self.contract_type == ContractType::Future,
+            ErrorCode::DefaultError,
+            "implied basis only defined for futures"
+        )?;
+
+        validate!(
+            oracle_price > 0,
+            ErrorCode::InvalidOracle,
+            "oracle_price={} <= 0",
+            oracle_price
+        )?;
+
+        let time_to_expiry = self.expiry_ts.safe_sub(now)?;
+        if time_to_expiry <= 0 {
+            return Ok(0);
+        }
+
+        let reserve_price = self.amm.reserve_price()?;
+
+        reserve_price
+            .cast::<i128>()?
+            .safe_sub(oracle_price.cast()?)?
+            .safe_mul(PERCENTAGE_PRECISION_I128)?
+            .safe_div(oracle_price.cast()?)?
+            .safe_mul(ONE_YEAR.cast()?)?
+            .safe_div(time_to_expiry.cast()?)?
+            .cast()
+    }
+
+    pub fn warm_up_complete(&self, now: i64, warm_up_duration: i64) -> bool {
+        now.saturating_sub(self.warm_up_start_ts) >= warm_up_duration
+    }
+
+    pub fn name_str(&self) -> String {
+        let name_len = self
+            .name
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |index| index + 1);
+
+        String::from_utf8_lossy(&self.name[..name_len]).into_owned()
+    }
+
+    pub fn set_name(&mut self, name: &str) -> DriftResult {
+        let name_bytes = name.as_bytes();
+        validate!(
+            name_bytes.len() <= self.name.len(),
+            ErrorCode::NameTooLong,
+            "name is {} bytes, max is {}",
+            name_bytes.len(),
+            self.name.len()
+        )?;
+
+        self.name = [0; 32];
+        self.name[..name_bytes.len()].copy_from_slice(name_bytes);
+
+        Ok(())
+    }
+
     pub fn is_reduce_only(&self) -> DriftResult<bool> {
         Ok(self.status == MarketStatus::ReduceOnly)
     }
 
+    pub fn validate_new_user(&self, max_users: u32) -> DriftResult {
+        validate!(
+            max_users == 0 || self.number_of_users < max_users,
+            ErrorCode::MaxUsersReached,
+            "market {} number_of_users={} >= max_users={}",
+            self.market_index,
+            self.number_of_users,
+            max_users
+        )
+    }
+
+    pub fn set_expiry(&mut self, expiry_ts: i64, now: i64) -> DriftResult {
+        validate!(
+            now < expiry_ts,
+            ErrorCode::InvalidExpiry,
+            "Market expiry ts must later than current clock timestamp (expiry_ts={}, now={})",
+            expiry_ts,
+            now
+        )?;
+
+        // automatically enter reduce only
+        self.status = MarketStatus::ReduceOnly;
+        self.expiry_ts = expiry_ts;
+
+        Ok(())
+    }
+
+    pub fn enter_settlement(&mut self, settlement_price: i64, now: i64) -> DriftResult {
+        validate!(
+            settlement_price > 0,
+            ErrorCode::InvalidOracle,
+            "settlement price must be positive (settlement_price={})",
+            settlement_price
+        )?;
+
+        validate!(
+            self.status.can_transition_to(MarketStatus::Settlement),
+            ErrorCode::DefaultError,
+            "market {} cannot enter settlement from status={:?}",
+            self.market_index,
+            self.status
+        )?;
+
+        self.status = MarketStatus::Settlement;
+        if self.expiry_ts == 0 {
+            self.expiry_ts = now;
+        }
+        self.expiry_price = settlement_price;
+
+        Ok(())
+    }
+
+    pub fn can_delist(&self) -> DriftResult<bool> {
+        Ok(self.status == MarketStatus::Settlement
+            && self.number_of_users == 0
+            && self.get_open_interest() == 0
+            && self.amm.user_lp_shares == 0)
+    }
+
+    pub fn amm_jit_allowed(&self) -> bool {
+        self.amm.amm_jit_is_active() && self.status == MarketStatus::Active
+    }
+
     pub fn is_operation_paused(&self, operation: PerpOperation) -> bool {
         PerpOperation::is_operation_paused(self.paused_operations, operation)
     }
 
+    pub fn set_amm_paused(&mut self, paused: bool) -> DriftResult {
+        // MarketStatus::AmmPaused is deprecated, so amm fills are toggled via PerpOperation::AmmFill
+        validate!(
+            self.status == MarketStatus::Active,
+            ErrorCode::DefaultError,
+            "can only toggle amm fills for active market (status={:?})",
+            self.status
+        )?;
+
+        if paused {
+            self.paused_operations |= PerpOperation::AmmFill as u8;
+        } else {
+            self.paused_operations &= !(PerpOperation::AmmFill as u8);
+        }
+
+        Ok(())
+    }
+
     pub fn has_too_much_drawdown(&self) -> DriftResult<bool> {
         let quote_drawdown_limit_breached = match self.contract_tier {
             ContractTier::A | ContractTier::B => {
@@ -345,6 +528,18 @@ impl PerpMarket {
         })
     }
 
+    pub fn max_curve_update_intensity(&self) -> u8 {
+        // (0, 100] is for repeg / formulaic k intensity
+        // (100, 200] is for reference price offset intensity
+        match self.contract_tier {
+            ContractTier::A => 100,
+            ContractTier::B => 150,
+            ContractTier::C => 175,
+            ContractTier::Speculative => 200,
+            ContractTier::Isolated => 200,
+        }
+    }
+
     pub fn get_sanitize_clamp_denominator(self) -> DriftResult<Option<i64>> {
         Ok(match self.contract_tier {
             ContractTier::A => Some(10_i64),   // 10%
@@ -355,6 +550,16 @@ impl PerpMarket {
         })
     }
 
+    /// oracle price clamped to the contract tier's sanitize band around the last oracle price.
+    /// the single price fills should use
+    pub fn fill_oracle_price(&self, oracle_price_data: &OraclePriceData) -> DriftResult<i64> {
+        amm::sanitize_new_price(
+            oracle_price_data.price,
+            self.amm.historical_oracle_data.last_oracle_price,
+            self.get_sanitize_clamp_denominator()?,
+        )
+    }
+
     pub fn get_auction_end_min_max_divisors(self) -> DriftResult<(u64, u64)> {
         Ok(match self.contract_tier {
             ContractTier::A => (1000, 50),          // 10 bps, 2%
@@ -365,6 +570,17 @@ impl PerpMarket {
         })
     }
 
+    pub fn liquidation_margin_buffer(&self) -> u32 {
+        // precision: MARGIN_PRECISION
+        match self.contract_tier {
+            ContractTier::A => 50,            // 50 bps
+            ContractTier::B => 100,           // 100 bps
+            ContractTier::C => 150,           // 150 bps
+            ContractTier::Speculative => 200, // 200 bps
+            ContractTier::Isolated => 250,    // 250 bps
+        }
+    }
+
     pub fn get_max_price_divergence_for_funding_rate(
         self,
         oracle_price_twap: i64,
@@ -473,6 +689,90 @@ impl PerpMarket {
         Ok(unrealized_asset_weight)
     }
 
+    /// initial margin ratio for the position size times its notional at the oracle price
+    /// precision: QUOTE_PRECISION
+    pub fn initial_margin_requirement(
+        &self,
+        base_asset_amount: i128,
+        oracle_price: i64,
+    ) -> DriftResult<u128> {
+        let base_asset_value = base_asset_amount
+            .unsigned_abs()
+            .safe_mul(oracle_price.unsigned_abs().cast()?)?
+            .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO)?;
+
+        let margin_ratio = self.get_margin_ratio(
+            base_asset_amount.unsigned_abs(),
+            MarginRequirementType::Initial,
+        )?;
+
+        base_asset_value
+            .safe_mul(margin_ratio.cast()?)?
+            .safe_div(MARGIN_PRECISION_U128)
+    }
+
+    /// the collateral a position needs on top of its unrealized pnl to stay above maintenance
+    pub fn maintenance_margin_requirement(
+        &self,
+        base_asset_amount: i128,
+        unrealized_pnl: i128,
+        oracle_price: i64,
+    ) -> DriftResult<u128> {
+        let base_asset_value = base_asset_amount
+            .unsigned_abs()
+            .safe_mul(oracle_price.unsigned_abs().cast()?)?
+            .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO)?;
+
+        let margin_ratio = self.get_margin_ratio(
+            base_asset_amount.unsigned_abs(),
+            MarginRequirementType::Maintenance,
+        )?;
+
+        let margin_requirement = base_asset_value
+            .safe_mul(margin_ratio.cast()?)?
+            .safe_div(MARGIN_PRECISION_U128)?;
+
+        let unrealized_asset_weight =
+            self.get_unrealized_asset_weight(unrealized_pnl, MarginRequirementType::Maintenance)?;
+
+        let weighted_unrealized_pnl = unrealized_pnl
+            .safe_mul(unrealized_asset_weight.cast()?)?
+            .safe_div(SPOT_WEIGHT_PRECISION.cast()?)?;
+
+        Ok(margin_requirement
+            .cast::<i128>()?
+            .safe_sub(weighted_unrealized_pnl)?
+            .max(0)
+            .unsigned_abs())
+    }
+
+    pub fn split_liquidation_fee(&self, total_fee: u128) -> DriftResult<(u128, u128)> {
+        if self.if_liquidation_fee == 0 {
+            return Ok((total_fee, 0));
+        }
+
+        let insurance_portion = total_fee
+            .safe_mul(self.if_liquidation_fee.cast()?)?
+            .safe_div(
+                self.liquidator_fee
+                    .cast::<u128>()?
+                    .safe_add(self.if_liquidation_fee.cast()?)?,
+            )?;
+        let liquidator_portion = total_fee.safe_sub(insurance_portion)?;
+
+        Ok((liquidator_portion, insurance_portion))
+    }
+
+    /// signed quote cost to the fee pool of moving sqrt_k to new_sqrt_k at the current price,
+    /// priced by cp_curve::adjust_k_cost on a copy of the market. negative is a rebate
+    pub fn k_adjustment_cost(&self, new_sqrt_k: u128) -> DriftResult<i128> {
+        let update_k_result =
+            cp_curve::get_update_k_result(self, bn::U192::from(new_sqrt_k), false)?;
+
+        let mut market_clone = *self;
+        cp_curve::adjust_k_cost(&mut market_clone, &update_k_result)
+    }
+
     pub fn get_open_interest(&self) -> u128 {
         self.amm
             .base_asset_amount_long
@@ -481,6 +781,76 @@ impl PerpMarket {
             .unsigned_abs()
     }
 
+    /// total_social_loss spread over open interest, in quote per base (QUOTE_PRECISION).
+    /// zero when there is no open interest to haircut
+    pub fn social_loss_per_base(&self) -> DriftResult<i128> {
+        let open_interest = self.get_open_interest();
+        if open_interest == 0 {
+            return Ok(0);
+        }
+
+        self.amm
+            .total_social_loss
+            .cast::<i128>()?
+            .safe_mul(AMM_RESERVE_PRECISION_I128)?
+            .safe_div(open_interest.cast()?)
+    }
+
+    pub fn set_max_open_interest(&mut self, new_max: u128) -> DriftResult {
+        // 0 removes the cap, nothing to check it against
+        if new_max == 0 {
+            self.amm.max_open_interest = 0;
+            return Ok(());
+        }
+
+        let open_interest = self.get_open_interest();
+        validate!(
+            new_max >= open_interest,
+            ErrorCode::MaxOpenInterest,
+            "new max_open_interest={} < current open interest={}",
+            new_max,
+            open_interest
+        )?;
+
+        // sqrt_k is the base reserve of a balanced curve (AMM_RESERVE_PRECISION == BASE_PRECISION).
+        // if the whole max oi ends up one-sided against the amm, capping it at a quarter of
+        // sqrt_k keeps the base reserve at >= 75% of its balanced size, i.e. the reserve price
+        // moves at most ~1.78x (1 / 0.75^2) instead of the curve being drained
+        validate!(
+            new_max.safe_mul(4)? <= self.amm.sqrt_k,
+            ErrorCode::MaxOpenInterest,
+            "new max_open_interest={} too large for sqrt_k={}",
+            new_max,
+            self.amm.sqrt_k
+        )?;
+
+        self.amm.max_open_interest = new_max;
+
+        Ok(())
+    }
+
+    pub fn lp_oi_fraction(&self) -> DriftResult<u32> {
+        let open_interest = self.get_open_interest();
+        if open_interest == 0 {
+            return Ok(0);
+        }
+
+        self.amm
+            .base_asset_amount_with_unsettled_lp
+            .unsigned_abs()
+            .safe_mul(BID_ASK_SPREAD_PRECISION_U128)?
+            .safe_div(open_interest)?
+            .min(BID_ASK_SPREAD_PRECISION_U128)
+            .cast()
+    }
+
+    pub fn remaining_insurance_capacity(&self) -> DriftResult<u64> {
+        Ok(self
+            .insurance_claim
+            .quote_max_insurance
+            .saturating_sub(self.insurance_claim.quote_settled_insurance))
+    }
+
     pub fn get_market_depth_for_funding_rate(&self) -> DriftResult<u64> {
         // base amount used on user orders for funding calculation
 
@@ -566,6 +936,127 @@ impl PerpMarket {
 
         Ok(true)
     }
+
+    pub fn breakeven_price(
+        &self,
+        base_asset_amount: i128,
+        quote_asset_amount: i128,
+        total_fees_paid: u128,
+    ) -> DriftResult<i64> {
+        validate!(
+            base_asset_amount != 0,
+            ErrorCode::UserHasNoPositionInMarket,
+            "cant compute breakeven price for zero base_asset_amount"
+        )?;
+
+        // pnl = base_asset_amount * price / PRICE_TIMES_AMM_TO_QUOTE + quote_asset_amount - total_fees_paid
+        // solve for pnl = 0
+        total_fees_paid
+            .cast::<i128>()?
+            .safe_sub(quote_asset_amount)?
+            .safe_mul(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128)?
+            .safe_div(base_asset_amount)?
+            .cast::<i64>()
+    }
+
+    pub fn worst_case_social_loss(
+        &self,
+        oracle_price: i64,
+        stress_move_bps: u32,
+    ) -> DriftResult<u128> {
+        validate!(
+            oracle_price > 0,
+            ErrorCode::InvalidOracle,
+            "oracle_price={} <= 0",
+            oracle_price
+        )?;
+
+        // amm takes the other side of base_asset_amount_with_amm, so the adverse move is
+        // whichever direction increases the value of the users' net position
+        let stressed_notional = self
+            .amm
+            .base_asset_amount_with_amm
+            .unsigned_abs()
+            .safe_mul(oracle_price.cast()?)?
+            .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO)?;
+
+        stressed_notional
+            .safe_mul(stress_move_bps.cast()?)?
+            .safe_div(ONE_BPS_DENOMINATOR.cast()?)
+    }
+
+    /// quote_max_insurance relative to the worst case social loss under the default stress move.
+    /// precision: MARGIN_PRECISION
+    pub fn insurance_coverage_ratio(&self, oracle_price: i64) -> DriftResult<u32> {
+        let worst_case_loss =
+            self.worst_case_social_loss(oracle_price, DEFAULT_SOCIAL_LOSS_STRESS_BPS)?;
+
+        if worst_case_loss == 0 {
+            return Ok(u32::MAX);
+        }
+
+        self.insurance_claim
+            .quote_max_insurance
+            .cast::<u128>()?
+            .safe_mul(MARGIN_PRECISION_U128)?
+            .safe_div(worst_case_loss)?
+            .min(u32::MAX as u128)
+            .cast()
+    }
+
+    pub fn pnl_pool_value(&self, quote_spot_market: &SpotMarket) -> DriftResult<u128> {
+        validate!(
+            quote_spot_market.market_index == self.pnl_pool.market_index,
+            ErrorCode::InvalidSpotMarketAccount,
+            "quote_spot_market.market_index={} != pnl_pool.market_index={}",
+            quote_spot_market.market_index,
+            self.pnl_pool.market_index
+        )?;
+
+        get_token_amount(
+            self.pnl_pool.scaled_balance,
+            quote_spot_market,
+            self.pnl_pool.balance_type(),
+        )
+    }
+
+    /// what can leave the pnl pool while still covering total_positive_unsettled_pnl owed to
+    /// winning positions. zero once the pool is already short of what's owed
+    /// precision: QUOTE_PRECISION
+    pub fn max_pnl_pool_withdraw(
+        &self,
+        quote_spot_market: &SpotMarket,
+        total_positive_unsettled_pnl: i128,
+    ) -> DriftResult<u128> {
+        let pnl_pool_value = self.pnl_pool_value(quote_spot_market)?;
+
+        Ok(pnl_pool_value.saturating_sub(total_positive_unsettled_pnl.max(0).unsigned_abs()))
+    }
+
+    /// pnl pool + fee pool + the notional of the amm's net inventory at the oracle price
+    /// precision: QUOTE_PRECISION
+    pub fn total_value_locked(
+        &self,
+        quote_spot_market: &SpotMarket,
+        oracle_price: i64,
+    ) -> DriftResult<u128> {
+        let pnl_pool_value = self.pnl_pool_value(quote_spot_market)?;
+
+        let fee_pool_value = get_token_amount(
+            self.amm.fee_pool.scaled_balance,
+            quote_spot_market,
+            self.amm.fee_pool.balance_type(),
+        )?;
+
+        let inventory_notional = self
+            .amm
+            .amm_inventory_notional(oracle_price)?
+            .unsigned_abs();
+
+        pnl_pool_value
+            .safe_add(fee_pool_value)?
+            .safe_add(inventory_notional)
+    }
 }
 
 #[cfg(test)]
@@ -655,6 +1146,27 @@ impl SpotBalance for PoolBalance {
     }
 }
 
+impl PoolBalance {
+    pub fn accrue_interest(
+        &mut self,
+        interest_rate_per_year: u64, // SPOT_RATE_PRECISION
+        seconds_elapsed: i64,
+    ) -> DriftResult {
+        if seconds_elapsed == 0 || interest_rate_per_year == 0 {
+            return Ok(());
+        }
+
+        let interest = self
+            .scaled_balance
+            .safe_mul(interest_rate_per_year.cast()?)?
+            .safe_mul(seconds_elapsed.cast()?)?
+            .safe_div(ONE_YEAR)?
+            .safe_div(SPOT_RATE_PRECISION)?;
+
+        self.increase_balance(interest)
+    }
+}
+
 #[assert_no_slop]
 #[zero_copy(unsafe)]
 #[derive(Debug, PartialEq, Eq)]
@@ -871,7 +1383,10 @@ pub struct AMM {
     pub net_unsettled_funding_pnl: i64,
     pub quote_asset_amount_with_unsettled_lp: i64,
     pub reference_price_offset: i32,
-    pub padding: [u8; 12],
+    pub padding: [u8; 4],
+    /// the last time the amm had a trade through update_volume_24h_ms
+    /// precision: milliseconds
+    pub last_trade_ts_ms: i64,
 }
 
 impl Default for AMM {
@@ -960,11 +1475,33 @@ impl Default for AMM {
             net_unsettled_funding_pnl: 0,
             quote_asset_amount_with_unsettled_lp: 0,
             reference_price_offset: 0,
-            padding: [0; 12],
+            padding: [0; 4],
+            last_trade_ts_ms: 0,
         }
     }
 }
 
+/// compact view of amm state for indexers
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
+pub struct AmmSnapshot {
+    /// precision: PRICE_PRECISION
+    pub reserve_price: u64,
+    /// precision: PRICE_PRECISION
+    pub bid_price: u64,
+    /// precision: PRICE_PRECISION
+    pub ask_price: u64,
+    /// precision: BID_ASK_SPREAD_PRECISION
+    pub long_spread: u32,
+    /// precision: BID_ASK_SPREAD_PRECISION
+    pub short_spread: u32,
+    /// precision: BASE_PRECISION
+    pub base_asset_amount_with_amm: i128,
+    /// precision: QUOTE_PRECISION
+    pub volume_24h: u64,
+    /// precision: FUNDING_RATE_PRECISION
+    pub last_funding_rate: i64,
+}
+
 impl AMM {
     pub fn get_fallback_price(
         self,
@@ -1018,6 +1555,20 @@ impl AMM {
         }
     }
 
+    pub fn bid_ask_twap(&self) -> (u64, u64) {
+        (self.last_bid_price_twap, self.last_ask_price_twap)
+    }
+
+    pub fn mid_twap(&self) -> DriftResult<u64> {
+        self.last_bid_price_twap
+            .safe_add(self.last_ask_price_twap)?
+            .safe_div(2)
+    }
+
+    pub fn bid_ask_twap_crossed(&self) -> bool {
+        self.last_bid_price_twap > self.last_ask_price_twap
+    }
+
     pub fn get_lower_bound_sqrt_k(self) -> DriftResult<u128> {
         Ok(self.sqrt_k.min(
             self.user_lp_shares
@@ -1027,12 +1578,53 @@ impl AMM {
         ))
     }
 
+    pub fn validate_order_constraints(&self) -> DriftResult {
+        validate!(
+            is_multiple_of_step_size(self.min_order_size, self.order_step_size)?,
+            ErrorCode::InvalidOrderMinOrderSize,
+            "min_order_size={} not a multiple of order_step_size={}",
+            self.min_order_size,
+            self.order_step_size
+        )?;
+
+        validate!(
+            self.max_position_size == 0
+                || is_multiple_of_step_size(self.max_position_size, self.order_step_size)?,
+            ErrorCode::InvalidOrderNotStepSizeMultiple,
+            "max_position_size={} not a multiple of order_step_size={}",
+            self.max_position_size,
+            self.order_step_size
+        )?;
+
+        Ok(())
+    }
+
+    pub fn derive_reserve_bounds(&self) -> DriftResult<(u128, u128)> {
+        // (min_base_asset_reserve, max_base_asset_reserve) = (sqrt_k / concentration_coef, sqrt_k * concentration_coef)
+        amm::calculate_bid_ask_bounds(self.concentration_coef, self.sqrt_k)
+    }
+
+    /// same as controller::amm::set_concentration_coef, but the amm is left untouched if the new
+    /// coef is rejected
+    pub fn set_concentration_coef(&mut self, new_coef: u128) -> DriftResult {
+        let mut amm = *self;
+        crate::controller::amm::set_concentration_coef(&mut amm, new_coef)?;
+        *self = amm;
+
+        Ok(())
+    }
+
     pub fn get_protocol_owned_position(self) -> DriftResult<i64> {
         self.base_asset_amount_with_amm
             .safe_add(self.base_asset_amount_with_unsettled_lp)?
             .cast::<i64>()
     }
 
+    pub fn net_quote_amount(&self) -> DriftResult<i128> {
+        self.quote_entry_amount_long
+            .safe_add(self.quote_entry_amount_short)
+    }
+
     pub fn get_max_reference_price_offset(self) -> DriftResult<i64> {
         if self.curve_update_intensity <= 100 {
             return Ok(0);
@@ -1208,6 +1800,44 @@ impl AMM {
         }
     }
 
+    /// lp shares that can still be minted before lp exposure could breach max_open_interest.
+    /// lps take the other side of user_lp_shares / sqrt_k of every amm fill after they mint, so
+    /// takers adding base to one side put that fraction of it on the other side once lps settle.
+    /// takers can add at most the room left under the cap on their own side, so the lp fraction
+    /// is capped at (smaller room / larger room). minting n shares adds n to both user_lp_shares
+    /// and sqrt_k, and inventory the amm already holds stays with the existing shares
+    pub fn max_lp_shares_mintable(&self) -> DriftResult<u128> {
+        if self.max_open_interest == 0 {
+            return Ok(u128::MAX);
+        }
+
+        let long_room = self
+            .max_open_interest
+            .saturating_sub(self.base_asset_amount_long.unsigned_abs());
+        let short_room = self
+            .max_open_interest
+            .saturating_sub(self.base_asset_amount_short.unsigned_abs());
+
+        let min_room = long_room.min(short_room);
+        let max_room = long_room.max(short_room);
+
+        // a side at the cap can't take any lp exposure
+        if min_room == 0 {
+            return Ok(0);
+        }
+
+        // takers on either side can't push lps past the room on the other, any fraction fits
+        if min_room == max_room {
+            return Ok(u128::MAX);
+        }
+
+        // (user_lp_shares + n) / (sqrt_k + n) <= min_room / max_room
+        Ok(min_room
+            .safe_mul(self.sqrt_k)?
+            .saturating_sub(self.user_lp_shares.safe_mul(max_room)?)
+            .safe_div(max_room.safe_sub(min_room)?)?)
+    }
+
     pub fn amm_jit_is_active(&self) -> bool {
         self.amm_jit_intensity > 0
     }
@@ -1216,29 +1846,304 @@ impl AMM {
         self.amm_jit_intensity > 100
     }
 
-    pub fn reserve_price(&self) -> DriftResult<u64> {
-        amm::calculate_price(
-            self.quote_asset_reserve,
-            self.base_asset_reserve,
-            self.peg_multiplier,
-        )
+    pub fn depth_at_prices(
+        &self,
+        price_levels: &[u64],
+        direction: PositionDirection,
+    ) -> DriftResult<Vec<u64>> {
+        let (max_bids, max_asks) = amm::calculate_market_open_bids_asks(self)?;
+        let max_depth = match direction {
+            PositionDirection::Long => max_bids.unsigned_abs(),
+            PositionDirection::Short => max_asks.unsigned_abs(),
+        }
+        .min(u64::MAX as u128)
+        .cast::<u64>()?;
+
+        // cumulative base the amm fills moving along the curve from the current reserves to each price
+        price_levels
+            .iter()
+            .map(|price_level| {
+                let (base_asset_amount, trade_direction) =
+                    amm_spread::calculate_base_asset_amount_to_trade_to_price(
+                        self,
+                        *price_level,
+                        direction,
+                    )?;
+
+                if trade_direction != direction {
+                    Ok(0)
+                } else {
+                    Ok(base_asset_amount.min(max_depth))
+                }
+            })
+            .collect()
     }
 
-    pub fn bid_price(&self, reserve_price: u64) -> DriftResult<u64> {
-        reserve_price
-            .cast::<u128>()?
-            .safe_mul(BID_ASK_SPREAD_PRECISION_U128.safe_sub(self.short_spread.cast()?)?)?
-            .safe_div(BID_ASK_SPREAD_PRECISION_U128)?
-            .cast()
+    /// (base_asset_reserve, quote_asset_reserve) shifted by the long or short spread (and the
+    /// reference price offset), so that their price is the ask for longs and the bid for shorts
+    pub fn spread_adjusted_reserves(
+        &self,
+        direction: PositionDirection,
+    ) -> DriftResult<(u128, u128)> {
+        amm_spread::calculate_spread_reserves(self, direction)
     }
 
-    pub fn ask_price(&self, reserve_price: u64) -> DriftResult<u64> {
-        reserve_price
-            .cast::<u128>()?
-            .safe_mul(BID_ASK_SPREAD_PRECISION_U128.safe_add(self.long_spread.cast()?)?)?
-            .safe_div(BID_ASK_SPREAD_PRECISION_U128)?
-            .cast::<u64>()
-    }
+    pub fn top_of_book_size(&self, direction: PositionDirection) -> DriftResult<u64> {
+        // base the amm quotes between the reserve price and its bid/ask
+        let (spread_base_asset_reserve, _) =
+            amm_spread::calculate_spread_reserves(self, direction)?;
+
+        let base_asset_amount = match direction {
+            PositionDirection::Long => self
+                .base_asset_reserve
+                .saturating_sub(spread_base_asset_reserve),
+            PositionDirection::Short => {
+                spread_base_asset_reserve.saturating_sub(self.base_asset_reserve)
+            }
+        };
+
+        base_asset_amount.cast()
+    }
+
+    pub fn avg_fill_price(
+        &self,
+        base_asset_amount: i128,
+        direction: PositionDirection,
+    ) -> DriftResult<u64> {
+        validate!(
+            base_asset_amount != 0,
+            ErrorCode::DefaultError,
+            "base_asset_amount must be non-zero"
+        )?;
+
+        let base_asset_amount = base_asset_amount.unsigned_abs();
+        let swap_direction = match direction {
+            PositionDirection::Long => SwapDirection::Remove,
+            PositionDirection::Short => SwapDirection::Add,
+        };
+
+        // same swap the fill does, against the spread reserves
+        let (base_asset_reserve_with_spread, quote_asset_reserve_with_spread) =
+            amm_spread::get_spread_reserves(self, direction)?;
+
+        let (new_quote_asset_reserve_with_spread, _) = amm::calculate_swap_output(
+            base_asset_amount,
+            base_asset_reserve_with_spread,
+            swap_direction,
+            self.sqrt_k,
+        )?;
+
+        let quote_asset_amount = amm::calculate_quote_asset_amount_swapped(
+            quote_asset_reserve_with_spread,
+            new_quote_asset_reserve_with_spread,
+            swap_direction,
+            self.peg_multiplier,
+        )?;
+
+        quote_asset_amount
+            .safe_mul(AMM_RESERVE_PRECISION)?
+            .safe_div(base_asset_amount)?
+            .cast()
+    }
+
+    /// whether the amm taking the other side of a liquidated position of base_asset_amount moves
+    /// the reserve price by no more than max_slippage_bps
+    pub fn can_absorb_liquidation(
+        &self,
+        base_asset_amount: i128,
+        max_slippage_bps: u32,
+    ) -> DriftResult<bool> {
+        if base_asset_amount == 0 {
+            return Ok(true);
+        }
+
+        // closing a long sells base into the amm, closing a short buys it out
+        let swap_direction = if base_asset_amount > 0 {
+            SwapDirection::Add
+        } else {
+            SwapDirection::Remove
+        };
+
+        if swap_direction == SwapDirection::Remove
+            && base_asset_amount.unsigned_abs() >= self.base_asset_reserve
+        {
+            return Ok(false);
+        }
+
+        let (new_quote_asset_reserve, new_base_asset_reserve) = amm::calculate_swap_output(
+            base_asset_amount.unsigned_abs(),
+            self.base_asset_reserve,
+            swap_direction,
+            self.sqrt_k,
+        )?;
+
+        let reserve_price_before = self.reserve_price()?;
+        let reserve_price_after = amm::calculate_price(
+            new_quote_asset_reserve,
+            new_base_asset_reserve,
+            self.peg_multiplier,
+        )?;
+
+        let slippage_bps = reserve_price_after
+            .cast::<i128>()?
+            .safe_sub(reserve_price_before.cast()?)?
+            .unsigned_abs()
+            .safe_mul(ONE_BPS_DENOMINATOR.cast()?)?
+            .safe_div(reserve_price_before.cast()?)?;
+
+        Ok(slippage_bps <= max_slippage_bps.cast()?)
+    }
+
+    /// the quote the amm pays (positive) or receives (negative) to trade base_asset_amount_with_amm
+    /// back to zero at the current reserves
+    pub fn quote_to_flatten(&self) -> DriftResult<i128> {
+        if self.base_asset_amount_with_amm == 0 {
+            return Ok(0);
+        }
+
+        // users' net long is the amm's short, which it buys back by taking base into the pool
+        let swap_direction = if self.base_asset_amount_with_amm > 0 {
+            SwapDirection::Add
+        } else {
+            SwapDirection::Remove
+        };
+
+        let (new_quote_asset_reserve, _) = amm::calculate_swap_output(
+            self.base_asset_amount_with_amm.unsigned_abs(),
+            self.base_asset_reserve,
+            swap_direction,
+            self.sqrt_k,
+        )?;
+
+        let quote_asset_amount = amm::calculate_quote_asset_amount_swapped(
+            self.quote_asset_reserve,
+            new_quote_asset_reserve,
+            swap_direction,
+            self.peg_multiplier,
+        )?
+        .cast::<i128>()?;
+
+        Ok(match swap_direction {
+            SwapDirection::Add => quote_asset_amount,
+            SwapDirection::Remove => -quote_asset_amount,
+        })
+    }
+
+    pub fn reserve_price(&self) -> DriftResult<u64> {
+        if self.base_asset_reserve == 0 {
+            msg!("cant calculate reserve price with base_asset_reserve == 0");
+            return Err(ErrorCode::InvalidAmmDetected);
+        }
+
+        amm::calculate_price(
+            self.quote_asset_reserve,
+            self.base_asset_reserve,
+            self.peg_multiplier,
+        )
+    }
+
+    pub fn quote_reserve_for_price(&self, target_price: u64) -> DriftResult<u128> {
+        validate!(
+            self.peg_multiplier > 0,
+            ErrorCode::InvalidAmmDetected,
+            "peg_multiplier must be positive"
+        )?;
+
+        // inverse of amm::calculate_price
+        bn::U192::from(target_price)
+            .safe_mul(bn::U192::from(self.base_asset_reserve))?
+            .safe_div(bn::U192::from(
+                self.peg_multiplier.safe_mul(PRICE_TO_PEG_PRECISION_RATIO)?,
+            ))?
+            .try_to_u128()
+    }
+
+    pub fn set_peg_multiplier(&mut self, new_peg: u128) -> DriftResult {
+        validate!(
+            new_peg > 0,
+            ErrorCode::DefaultError,
+            "peg_multiplier must be positive"
+        )?;
+
+        if self.peg_multiplier > 0 {
+            // guard against fat-fingered pegs by limiting a single update to 10x either way
+            validate!(
+                new_peg <= self.peg_multiplier.safe_mul(10)?
+                    && new_peg.safe_mul(10)? >= self.peg_multiplier,
+                ErrorCode::DefaultError,
+                "new_peg={} more than 10x away from peg_multiplier={}",
+                new_peg,
+                self.peg_multiplier
+            )?;
+        }
+
+        self.peg_multiplier = new_peg;
+
+        Ok(())
+    }
+
+    /// most base a single fill can take from the amm, per max_fill_reserve_fraction
+    pub fn max_base_per_fill(&self) -> DriftResult<u64> {
+        self.base_asset_reserve
+            .safe_div(self.max_fill_reserve_fraction.cast()?)?
+            .min(u64::MAX as u128)
+            .cast()
+    }
+
+    /// reserve price once base_asset_amount_with_amm is swapped back to zero (the terminal price)
+    pub fn inventory_flip_price(&self) -> DriftResult<u64> {
+        let (terminal_price, _, _) = amm::calculate_terminal_price_and_reserves(self)?;
+        Ok(terminal_price)
+    }
+
+    pub fn reserve_price_within_oracle_band(
+        &self,
+        oracle_price: i64,
+        max_divergence_bps: u32,
+    ) -> DriftResult<bool> {
+        validate!(
+            oracle_price > 0,
+            ErrorCode::InvalidOracle,
+            "oracle_price={} <= 0",
+            oracle_price
+        )?;
+
+        let reserve_price = self.reserve_price()?;
+
+        let divergence_bps = reserve_price
+            .cast::<i64>()?
+            .safe_sub(oracle_price)?
+            .unsigned_abs()
+            .cast::<u128>()?
+            .safe_mul(ONE_BPS_DENOMINATOR.cast()?)?
+            .safe_div(oracle_price.cast()?)?;
+
+        Ok(divergence_bps <= max_divergence_bps.cast()?)
+    }
+
+    pub fn should_update_curve(&self, oracle_price: i64, threshold_bps: u32) -> DriftResult<bool> {
+        if self.curve_update_intensity == 0 {
+            return Ok(false);
+        }
+
+        Ok(!self.reserve_price_within_oracle_band(oracle_price, threshold_bps)?)
+    }
+
+    pub fn bid_price(&self, reserve_price: u64) -> DriftResult<u64> {
+        reserve_price
+            .cast::<u128>()?
+            .safe_mul(BID_ASK_SPREAD_PRECISION_U128.safe_sub(self.short_spread.cast()?)?)?
+            .safe_div(BID_ASK_SPREAD_PRECISION_U128)?
+            .cast()
+    }
+
+    pub fn ask_price(&self, reserve_price: u64) -> DriftResult<u64> {
+        reserve_price
+            .cast::<u128>()?
+            .safe_mul(BID_ASK_SPREAD_PRECISION_U128.safe_add(self.long_spread.cast()?)?)?
+            .safe_div(BID_ASK_SPREAD_PRECISION_U128)?
+            .cast::<u64>()
+    }
 
     pub fn bid_ask_price(&self, reserve_price: u64) -> DriftResult<(u64, u64)> {
         let bid_price = self.bid_price(reserve_price)?;
@@ -1246,6 +2151,57 @@ impl AMM {
         Ok((bid_price, ask_price))
     }
 
+    /// bid_ask_price with the supplied spreads in place of the stored long_spread/short_spread
+    pub fn bid_ask_price_with_spread(
+        &self,
+        reserve_price: u64,
+        long_spread: u32,
+        short_spread: u32,
+    ) -> DriftResult<(u64, u64)> {
+        AMM {
+            long_spread,
+            short_spread,
+            ..*self
+        }
+        .bid_ask_price(reserve_price)
+    }
+
+    pub fn snapshot(&self) -> DriftResult<AmmSnapshot> {
+        let reserve_price = self.reserve_price()?;
+        let (bid_price, ask_price) = self.bid_ask_price(reserve_price)?;
+
+        Ok(AmmSnapshot {
+            reserve_price,
+            bid_price,
+            ask_price,
+            long_spread: self.long_spread,
+            short_spread: self.short_spread,
+            base_asset_amount_with_amm: self.base_asset_amount_with_amm,
+            volume_24h: self.volume_24h,
+            last_funding_rate: self.last_funding_rate,
+        })
+    }
+
+    pub fn effective_taker_spread(
+        &self,
+        direction: PositionDirection,
+        taker_fee_bps: u32,
+    ) -> DriftResult<u32> {
+        let spread = match direction {
+            PositionDirection::Long => self.long_spread,
+            PositionDirection::Short => self.short_spread,
+        };
+
+        // BID_ASK_SPREAD_PRECISION -> bps
+        let spread_bps = spread.safe_div(
+            BID_ASK_SPREAD_PRECISION
+                .cast::<u32>()?
+                .safe_div(ONE_BPS_DENOMINATOR)?,
+        )?;
+
+        spread_bps.safe_add(taker_fee_bps)
+    }
+
     pub fn last_ask_premium(&self) -> DriftResult<i64> {
         let reserve_price = self.reserve_price()?;
         let ask_price = self.ask_price(reserve_price)?.cast::<i64>()?;
@@ -1269,6 +2225,42 @@ impl AMM {
         Ok(can_lower)
     }
 
+    /// max oracle delay (in slots) tolerated for the oracle source's update cadence
+    pub fn max_oracle_delay(&self) -> i64 {
+        match self.oracle_source {
+            OracleSource::Pyth
+            | OracleSource::Pyth1K
+            | OracleSource::Pyth1M
+            | OracleSource::PythStableCoin => 10,
+            OracleSource::Switchboard | OracleSource::Prelaunch => 30,
+            OracleSource::QuoteAsset => i64::MAX,
+        }
+    }
+
+    /// whether the oracle has to be cranked before use. prelaunch oracles are updated from the
+    /// amm by the program itself, push oracles (pyth, switchboard) are updated off-chain
+    pub fn oracle_requires_update(&self) -> bool {
+        match self.oracle_source {
+            OracleSource::Prelaunch => true,
+            OracleSource::Pyth
+            | OracleSource::Pyth1K
+            | OracleSource::Pyth1M
+            | OracleSource::PythStableCoin
+            | OracleSource::Switchboard
+            | OracleSource::QuoteAsset => false,
+        }
+    }
+
+    pub fn validate_oracle_account(&self, oracle_account: &AccountInfo) -> DriftResult {
+        validate!(
+            oracle_account.key == &self.oracle,
+            ErrorCode::InvalidOracle,
+            "oracle account {} does not match amm oracle {}",
+            oracle_account.key,
+            self.oracle
+        )
+    }
+
     pub fn get_oracle_twap(
         &self,
         price_oracle: &AccountInfo,
@@ -1329,18 +2321,340 @@ impl AMM {
 
         amm::update_amm_long_short_intensity(self, now, quote_asset_amount, position_direction)?;
 
-        self.volume_24h = stats::calculate_rolling_sum(
-            self.volume_24h,
+        self.update_volume_24h_rolling_sum(quote_asset_amount, since_last, TWENTY_FOUR_HOUR)?;
+
+        self.last_trade_ts = now;
+
+        Ok(())
+    }
+
+    /// same as update_volume_24h but decays by milliseconds elapsed, so trades within the same
+    /// second aren't all treated as a second apart
+    pub fn update_volume_24h_ms(
+        &mut self,
+        quote_asset_amount: u64,
+        position_direction: PositionDirection,
+        now_ms: i64,
+    ) -> DriftResult {
+        let now = now_ms.safe_div(1000)?;
+        // last_trade_ts is only second precision, use it if a trade came through update_volume_24h since
+        let last_trade_ts_ms = self
+            .last_trade_ts_ms
+            .max(self.last_trade_ts.safe_mul(1000)?);
+        let since_last_ms = max(1_i64, now_ms.safe_sub(last_trade_ts_ms)?);
+
+        amm::update_amm_long_short_intensity(self, now, quote_asset_amount, position_direction)?;
+
+        self.update_volume_24h_rolling_sum(
             quote_asset_amount,
-            since_last,
-            TWENTY_FOUR_HOUR,
+            since_last_ms,
+            TWENTY_FOUR_HOUR.safe_mul(1000)?,
         )?;
 
         self.last_trade_ts = now;
+        self.last_trade_ts_ms = now_ms;
 
         Ok(())
     }
 
+    fn update_volume_24h_rolling_sum(
+        &mut self,
+        quote_asset_amount: u64,
+        since_last: i64,
+        window: i64,
+    ) -> DriftResult {
+        self.volume_24h = match stats::calculate_rolling_sum(
+            self.volume_24h,
+            quote_asset_amount,
+            since_last,
+            window,
+        ) {
+            Ok(volume_24h) => volume_24h,
+            Err(ErrorCode::MathError) => {
+                // dont block the fill on volume stat overflow
+                msg!(
+                    "volume_24h overflow (volume_24h={}, quote_asset_amount={}), saturating",
+                    self.volume_24h,
+                    quote_asset_amount
+                );
+                u64::MAX
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(())
+    }
+
+    /// annualized last_funding_rate as a fraction of the oracle twap
+    /// precision: PRICE_PRECISION (1e6 = 100%)
+    pub fn funding_rate_apr(&self) -> DriftResult<i64> {
+        validate!(
+            self.funding_period > 0,
+            ErrorCode::DefaultError,
+            "funding_period={} <= 0",
+            self.funding_period
+        )?;
+
+        let oracle_price_twap = self.historical_oracle_data.last_oracle_price_twap;
+        validate!(
+            oracle_price_twap > 0,
+            ErrorCode::InvalidOracle,
+            "last_oracle_price_twap={} <= 0",
+            oracle_price_twap
+        )?;
+
+        let funding_periods_per_year = TWENTY_FOUR_HOUR
+            .safe_div(self.funding_period)?
+            .safe_mul(365)?
+            .cast::<i128>()?;
+
+        self.last_funding_rate
+            .cast::<i128>()?
+            .safe_mul(funding_periods_per_year)?
+            .safe_mul(PRICE_PRECISION_I128)?
+            .safe_div(
+                oracle_price_twap
+                    .cast::<i128>()?
+                    .safe_mul(FUNDING_RATE_BUFFER_I128)?,
+            )?
+            .cast()
+    }
+
+    /// signed quote notional of base_asset_amount at the reserve price
+    pub fn base_to_quote_notional(&self, base_asset_amount: i128) -> DriftResult<i128> {
+        base_asset_amount
+            .safe_mul(self.reserve_price()?.cast()?)?
+            .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128)
+    }
+
+    pub fn amm_inventory_notional(&self, oracle_price: i64) -> DriftResult<i128> {
+        self.base_asset_amount_with_amm
+            .safe_mul(oracle_price.cast()?)?
+            .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO_I128)
+    }
+
+    pub fn amm_leverage(&self, oracle_price: i64) -> DriftResult<u32> {
+        let inventory_notional = self.amm_inventory_notional(oracle_price)?.unsigned_abs();
+
+        if inventory_notional == 0 {
+            return Ok(0);
+        }
+
+        // no fee pool backing the inventory means unbounded leverage
+        if self.total_fee_minus_distributions <= 0 {
+            return Ok(u32::MAX);
+        }
+
+        inventory_notional
+            .safe_mul(MARGIN_PRECISION_U128)?
+            .safe_div(self.total_fee_minus_distributions.unsigned_abs())?
+            .min(u32::MAX as u128)
+            .cast()
+    }
+
+    pub fn cumulative_funding_delta(
+        &self,
+        since_cumulative_long: i128,
+        since_cumulative_short: i128,
+        direction: PositionDirection,
+    ) -> i128 {
+        match direction {
+            PositionDirection::Long => self
+                .cumulative_funding_rate_long
+                .saturating_sub(since_cumulative_long),
+            PositionDirection::Short => self
+                .cumulative_funding_rate_short
+                .saturating_sub(since_cumulative_short),
+        }
+    }
+
+    pub fn net_revenue_rate(&self, now: i64) -> DriftResult<i64> {
+        let elapsed = now.safe_sub(self.last_funding_rate_ts)?;
+        if elapsed <= 0 {
+            return Ok(0);
+        }
+
+        self.net_revenue_since_last_funding.safe_div(elapsed)
+    }
+
+    /// spread to add on top of base_spread for recent mark volatility: half of mark_std relative
+    /// to the reserve price, same as the std term in calculate_long_short_vol_spread.
+    /// precision: BID_ASK_SPREAD_PRECISION
+    pub fn volatility_spread_component(&self) -> DriftResult<u32> {
+        let reserve_price = self.reserve_price()?;
+
+        self.mark_std
+            .cast::<u128>()?
+            .safe_mul(BID_ASK_SPREAD_PRECISION_U128)?
+            .safe_div(reserve_price.cast()?)?
+            .safe_div(2)?
+            .min(u32::MAX as u128)
+            .cast()
+    }
+
+    /// annualized volatility estimate from mark_std, which tracks the mark's deviation from its
+    /// twap over roughly an hour: iv = mark_std / reserve_price * sqrt(24 * 365).
+    /// zero without any volume_24h, since mark_std only moves on fills.
+    /// precision: PERCENTAGE_PRECISION
+    pub fn implied_volatility(&self) -> DriftResult<u32> {
+        if self.volume_24h == 0 {
+            return Ok(0);
+        }
+
+        let reserve_price = self.reserve_price()?;
+
+        self.mark_std
+            .cast::<u128>()?
+            .safe_mul(PERCENTAGE_PRECISION)?
+            .safe_mul(9359)?
+            .safe_div(100)? // sqrt(8760) ~= 93.59
+            .safe_div(reserve_price.cast()?)?
+            .min(u32::MAX as u128)
+            .cast()
+    }
+
+    /// (long_intensity_volume - short_intensity_volume) / (long + short), the skew of recent
+    /// taker flow. positive when more volume came from longs.
+    /// precision: BID_ASK_SPREAD_PRECISION
+    pub fn flow_imbalance(&self) -> DriftResult<i64> {
+        let total_volume = self
+            .long_intensity_volume
+            .cast::<i128>()?
+            .safe_add(self.short_intensity_volume.cast()?)?;
+
+        if total_volume == 0 {
+            return Ok(0);
+        }
+
+        self.long_intensity_volume
+            .cast::<i128>()?
+            .safe_sub(self.short_intensity_volume.cast()?)?
+            .safe_mul(BID_ASK_SPREAD_PRECISION_I128)?
+            .safe_div(total_volume)?
+            .cast()
+    }
+
+    /// long_spread and short_spread with the side taking more flow widened by flow_imbalance,
+    /// e.g. +50% skew to longs makes long_spread 1.5x. each side is capped at max_spread
+    pub fn asymmetric_spread(&self) -> DriftResult<(u32, u32)> {
+        let flow_imbalance = self.flow_imbalance()?;
+
+        let widen = |spread: u32, skew: i64| -> DriftResult<u32> {
+            let widened = spread.cast::<u64>()?.safe_add(
+                spread
+                    .cast::<u64>()?
+                    .safe_mul(skew.max(0).unsigned_abs())?
+                    .safe_div(BID_ASK_SPREAD_PRECISION)?,
+            )?;
+            widened.min(self.max_spread.max(spread).cast()?).cast()
+        };
+
+        let long_spread = widen(self.long_spread, flow_imbalance)?;
+        let short_spread = widen(self.short_spread, -flow_imbalance)?;
+
+        Ok((long_spread, short_spread))
+    }
+
+    pub fn spread_floor(&self) -> DriftResult<u32> {
+        // conf pct is one-sided, so double it to compare against the full base spread
+        // (same as calculate_max_target_spread)
+        let conf_spread = self
+            .last_oracle_conf_pct
+            .safe_mul(2)?
+            .min(BID_ASK_SPREAD_PRECISION);
+
+        Ok(self.base_spread.max(conf_spread.cast()?))
+    }
+
+    pub fn order_improves_amm(&self, direction: PositionDirection) -> bool {
+        // base_asset_amount_with_amm is the users' net position, so a taker improves the amm
+        // inventory by trading against it
+        match direction {
+            PositionDirection::Long => self.base_asset_amount_with_amm < 0,
+            PositionDirection::Short => self.base_asset_amount_with_amm > 0,
+        }
+    }
+
+    /// last_funding_rate_long - last_funding_rate_short. non-zero when funding was capped for the
+    /// side with more open interest and the amm subsidized the other
+    pub fn funding_rate_imbalance(&self) -> DriftResult<i64> {
+        self.last_funding_rate_long
+            .safe_sub(self.last_funding_rate_short)
+    }
+
+    /// funding from the last funding period on base_asset_amount_per_lp, i.e. per lp share.
+    /// positive means lps receive
+    pub fn lp_funding_per_share(&self) -> DriftResult<i128> {
+        let funding_rate_delta = if self.base_asset_amount_per_lp > 0 {
+            self.last_funding_rate_long
+        } else {
+            self.last_funding_rate_short
+        };
+
+        calculate_funding_payment_in_quote_precision(
+            funding_rate_delta.cast()?,
+            self.base_asset_amount_per_lp,
+        )
+    }
+
+    pub fn amm_funding_payment(&self) -> DriftResult<i128> {
+        // amm takes the other side of base_asset_amount_with_amm, so its payment is the negation
+        // of what users' net position pays for the last funding period. positive means amm receives
+        let net_user_funding_payment = calculate_funding_payment_in_quote_precision(
+            self.last_funding_rate.cast()?,
+            self.base_asset_amount_with_amm,
+        )?;
+
+        Ok(-net_user_funding_payment)
+    }
+
+    pub fn can_update_curve(&self, current_slot: u64, cooldown_slots: u64) -> bool {
+        if self.curve_update_intensity == 0 {
+            return false;
+        }
+
+        current_slot.saturating_sub(self.last_update_slot) >= cooldown_slots
+    }
+
+    pub fn fee_tier_for_volume(&self) -> u8 {
+        if self.volume_24h >= VOLUME_FEE_TIER_4_THRESHOLD {
+            4
+        } else if self.volume_24h >= VOLUME_FEE_TIER_3_THRESHOLD {
+            3
+        } else if self.volume_24h >= VOLUME_FEE_TIER_2_THRESHOLD {
+            2
+        } else if self.volume_24h >= VOLUME_FEE_TIER_1_THRESHOLD {
+            1
+        } else {
+            0
+        }
+    }
+
+    pub fn funding_payment(
+        &self,
+        base_asset_amount: i128,
+        last_cumulative_funding: i128,
+    ) -> DriftResult<i128> {
+        if base_asset_amount == 0 {
+            return Ok(0);
+        }
+
+        let amm_cumulative_funding_rate = if base_asset_amount > 0 {
+            self.cumulative_funding_rate_long
+        } else {
+            self.cumulative_funding_rate_short
+        };
+
+        let funding_rate_delta = amm_cumulative_funding_rate.safe_sub(last_cumulative_funding)?;
+
+        if funding_rate_delta == 0 {
+            return Ok(0);
+        }
+
+        // negative means the position owes funding
+        calculate_funding_payment_in_quote_precision(funding_rate_delta, base_asset_amount)
+    }
+
     pub fn get_new_oracle_conf_pct(
         &self,
         confidence: u64,    // price precision
@@ -1370,9 +2684,132 @@ impl AMM {
             .max(confidence_lower_bound))
     }
 
+    /// ewma of last_oracle_conf_pct over five minutes, so a single conf spike only partially
+    /// moves the stored value. last_conf_update_ts is when the conf was last folded in; the amm
+    /// doesn't store it, so the caller must pass now the next time or the same elapsed time is
+    /// weighted in again
+    pub fn update_oracle_conf(
+        &mut self,
+        new_conf_pct: u64,
+        last_conf_update_ts: i64,
+        now: i64,
+    ) -> DriftResult {
+        let since_last = now.safe_sub(last_conf_update_ts)?.max(0);
+        let from_start = FIVE_MINUTE.cast::<i64>()?.safe_sub(since_last)?.max(1);
+
+        self.last_oracle_conf_pct = stats::calculate_weighted_average(
+            self.last_oracle_conf_pct.cast()?,
+            new_conf_pct.cast()?,
+            from_start,
+            since_last,
+        )?
+        .cast()?;
+
+        Ok(())
+    }
+
     pub fn is_recent_oracle_valid(&self, current_slot: u64) -> DriftResult<bool> {
         Ok(self.last_oracle_valid && current_slot == self.last_update_slot)
     }
+
+    /// an empty fee pool is only expected when the amm hasn't booked any fees it still holds
+    pub fn fee_pool_solvent(&self) -> bool {
+        self.fee_pool.scaled_balance > 0 || self.total_fee_minus_distributions <= 0
+    }
+
+    /// tops an insolvent fee pool back up with up to insurance_backstop quote tokens, capped at
+    /// total_fee_minus_distributions. returns the amount pulled, which the caller must transfer
+    /// into the quote spot market vault
+    pub fn reconcile_fee_pool(
+        &mut self,
+        quote_spot_market: &mut SpotMarket,
+        insurance_backstop: u128,
+    ) -> DriftResult<u128> {
+        if self.fee_pool_solvent() {
+            return Ok(0);
+        }
+
+        let amount_pulled =
+            insurance_backstop.min(self.total_fee_minus_distributions.unsigned_abs());
+
+        if amount_pulled > 0 {
+            update_spot_balances(
+                amount_pulled,
+                &SpotBalanceType::Deposit,
+                quote_spot_market,
+                &mut self.fee_pool,
+                false,
+            )?;
+        }
+
+        Ok(amount_pulled)
+    }
+
+    pub fn apply_social_loss(&mut self, loss: u128) -> DriftResult<u128> {
+        if loss == 0 {
+            return Ok(0);
+        }
+
+        self.total_social_loss = self.total_social_loss.safe_add(loss)?;
+        self.total_fee_minus_distributions = self
+            .total_fee_minus_distributions
+            .safe_sub(loss.cast::<i128>()?)?;
+
+        Ok(loss)
+    }
+
+    /// checks the fee fields against each other. there is no upper bound on
+    /// total_fee_minus_distributions: it is total_fee - total_fee_withdrawn plus the pnl the amm
+    /// realized (see realized_pnl_to_fees), so it can exceed total_fee on a profitable amm
+    pub fn validate_fee_accounting(&self) -> DriftResult {
+        // total_fee is what the market keeps after filler/referrer rewards, so it can't exceed
+        // the gross fees from the spread and the exchange fee schedule
+        let gross_fee = self
+            .total_mm_fee
+            .safe_add(self.total_exchange_fee.cast()?)?;
+        validate!(
+            self.total_fee <= gross_fee,
+            ErrorCode::InvalidAmmDetected,
+            "total_fee={} > total_mm_fee={} + total_exchange_fee={}",
+            self.total_fee,
+            self.total_mm_fee,
+            self.total_exchange_fee
+        )?;
+
+        Ok(())
+    }
+
+    /// pnl the amm has realized into the fee pool accounting, i.e. everything in
+    /// total_fee_minus_distributions that didn't come from fees or leave as withdraws:
+    /// total_fee_minus_distributions + total_fee_withdrawn - total_fee
+    pub fn realized_pnl_to_fees(&self) -> DriftResult<i128> {
+        self.total_fee_minus_distributions
+            .safe_add(self.total_fee_withdrawn.cast()?)?
+            .safe_sub(self.total_fee)
+    }
+
+    /// fees the market earned but paid back out: total_mm_fee + total_exchange_fee - total_fee.
+    /// for amm fills this is maker rebates to post only orders plus filler and referrer rewards.
+    /// matched fills only book fee_to_market (already net of the maker rebate), so their rebates
+    /// are not included
+    pub fn total_maker_rebates(&self) -> DriftResult<i128> {
+        self.total_mm_fee
+            .safe_add(self.total_exchange_fee.cast()?)?
+            .safe_sub(self.total_fee)
+    }
+
+    /// share of total_fee captured by the amm spread, in bps; the rest came from exchange fees
+    pub fn amm_fee_share(&self) -> DriftResult<u32> {
+        if self.total_fee <= 0 {
+            return Ok(0);
+        }
+
+        self.total_mm_fee
+            .max(0)
+            .safe_mul(ONE_BPS_DENOMINATOR.cast()?)?
+            .safe_div(self.total_fee)?
+            .cast()
+    }
 }
 
 #[cfg(test)]
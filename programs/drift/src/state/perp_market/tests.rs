@@ -1,7 +1,14 @@
 mod amm {
+    use crate::controller::position::PositionDirection;
+    use crate::error::ErrorCode;
+    use crate::math::amm::calculate_price;
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
     use crate::state::perp_market::AMM;
     use crate::{
-        AMM_RESERVE_PRECISION, BID_ASK_SPREAD_PRECISION, PEG_PRECISION, PRICE_PRECISION_I64,
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BID_ASK_SPREAD_PRECISION,
+        FUNDING_RATE_PRECISION_I128, MARGIN_PRECISION, MAX_CONCENTRATION_COEFFICIENT,
+        PEG_PRECISION, PRICE_PRECISION_I64, PRICE_PRECISION_U64, QUOTE_PRECISION,
+        QUOTE_PRECISION_I128, QUOTE_PRECISION_I64, QUOTE_PRECISION_U64,
     };
 
     #[test]
@@ -39,4 +46,2298 @@ mod amm {
 
         assert_eq!(discount, 10000000); // $1
     }
+
+    #[test]
+    fn derive_reserve_bounds() {
+        let amm = AMM {
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            concentration_coef: MAX_CONCENTRATION_COEFFICIENT,
+            ..AMM::default()
+        };
+
+        let (min_base_asset_reserve, max_base_asset_reserve) = amm.derive_reserve_bounds().unwrap();
+
+        assert_eq!(min_base_asset_reserve, 70711356244);
+        assert_eq!(max_base_asset_reserve, 141420000000);
+
+        // bounds are symmetric (geometrically) around sqrt_k
+        assert_eq!(
+            min_base_asset_reserve * max_base_asset_reserve / amm.sqrt_k,
+            amm.sqrt_k
+        );
+    }
+
+    #[test]
+    fn validate_order_constraints() {
+        let mut amm = AMM {
+            order_step_size: 1000,
+            min_order_size: 1000,
+            max_position_size: 0,
+            ..AMM::default()
+        };
+        assert!(amm.validate_order_constraints().is_ok());
+
+        amm.max_position_size = 1_000_000;
+        assert!(amm.validate_order_constraints().is_ok());
+
+        amm.min_order_size = 1500;
+        assert_eq!(
+            amm.validate_order_constraints(),
+            Err(ErrorCode::InvalidOrderMinOrderSize)
+        );
+
+        amm.min_order_size = 2000;
+        amm.max_position_size = 1_000_500;
+        assert_eq!(
+            amm.validate_order_constraints(),
+            Err(ErrorCode::InvalidOrderNotStepSizeMultiple)
+        );
+    }
+
+    #[test]
+    fn set_concentration_coef() {
+        // users net short 5, which the amm holds as 5 extra base on top of its terminal 100
+        let mut amm = AMM {
+            base_asset_reserve: 105 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 95238095238,
+            base_asset_amount_with_amm: -5 * BASE_PRECISION_I128,
+            ..AMM::default_test()
+        };
+        let amm_before = amm;
+
+        // tightened bounds of [~99.01, 101] around the terminal reserve can't unload the inventory
+        let result = amm.set_concentration_coef(1_010_000);
+        assert_eq!(result, Err(ErrorCode::InvalidConcentrationCoef));
+        assert_eq!(amm, amm_before);
+
+        let result = amm.set_concentration_coef(1_000_000);
+        assert_eq!(result, Err(ErrorCode::InvalidConcentrationCoef));
+        assert_eq!(amm, amm_before);
+
+        amm.set_concentration_coef(1_200_000).unwrap();
+        assert_eq!(amm.concentration_coef, 1_200_000);
+        assert_eq!(amm.min_base_asset_reserve, 83333333333);
+        assert_eq!(amm.max_base_asset_reserve, 120 * AMM_RESERVE_PRECISION);
+    }
+
+    #[test]
+    fn depth_at_prices() {
+        let amm = AMM::default_test();
+        let reserve_price = amm.reserve_price().unwrap();
+        assert_eq!(reserve_price, PRICE_PRECISION_U64);
+
+        let ask_levels = [
+            reserve_price - PRICE_PRECISION_U64 / 100,
+            reserve_price + PRICE_PRECISION_U64 / 100,
+            reserve_price + PRICE_PRECISION_U64 / 50,
+            reserve_price + PRICE_PRECISION_U64 / 10,
+        ];
+
+        let depth = amm
+            .depth_at_prices(&ask_levels, PositionDirection::Long)
+            .unwrap();
+
+        assert_eq!(depth.len(), ask_levels.len());
+        // no asks below reserve price
+        assert_eq!(depth[0], 0);
+        assert!(depth[1] > 0);
+        assert!(depth[1] < depth[2]);
+        assert!(depth[2] < depth[3]);
+
+        let bid_levels = [
+            reserve_price - PRICE_PRECISION_U64 / 100,
+            reserve_price - PRICE_PRECISION_U64 / 10,
+        ];
+
+        let depth = amm
+            .depth_at_prices(&bid_levels, PositionDirection::Short)
+            .unwrap();
+
+        assert!(depth[0] > 0);
+        assert!(depth[0] < depth[1]);
+    }
+
+    #[test]
+    fn top_of_book_size() {
+        let mut amm = AMM::default_test();
+
+        amm.long_spread = (BID_ASK_SPREAD_PRECISION / 100) as u32;
+        amm.short_spread = (BID_ASK_SPREAD_PRECISION / 100) as u32;
+        let narrow_ask_size = amm.top_of_book_size(PositionDirection::Long).unwrap();
+        let narrow_bid_size = amm.top_of_book_size(PositionDirection::Short).unwrap();
+
+        amm.long_spread = (BID_ASK_SPREAD_PRECISION / 50) as u32;
+        amm.short_spread = (BID_ASK_SPREAD_PRECISION / 50) as u32;
+        let wide_ask_size = amm.top_of_book_size(PositionDirection::Long).unwrap();
+        let wide_bid_size = amm.top_of_book_size(PositionDirection::Short).unwrap();
+
+        assert!(narrow_ask_size > 0);
+        assert!(narrow_bid_size > 0);
+        assert!(wide_ask_size > narrow_ask_size);
+        assert!(wide_bid_size > narrow_bid_size);
+
+        amm.long_spread = 0;
+        assert_eq!(amm.top_of_book_size(PositionDirection::Long).unwrap(), 0);
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut amm = AMM::default_btc_test();
+        amm.long_spread = (BID_ASK_SPREAD_PRECISION / 100) as u32;
+        amm.short_spread = (BID_ASK_SPREAD_PRECISION / 200) as u32;
+        amm.volume_24h = 1_000_000 * QUOTE_PRECISION_U64;
+
+        let snapshot = amm.snapshot().unwrap();
+        let reserve_price = amm.reserve_price().unwrap();
+
+        assert_eq!(snapshot.reserve_price, reserve_price);
+        assert_eq!(
+            (snapshot.bid_price, snapshot.ask_price),
+            amm.bid_ask_price(reserve_price).unwrap()
+        );
+        assert!(snapshot.bid_price < snapshot.reserve_price);
+        assert!(snapshot.ask_price > snapshot.reserve_price);
+        assert_eq!(
+            snapshot.base_asset_amount_with_amm,
+            amm.base_asset_amount_with_amm
+        );
+        assert_eq!(snapshot.volume_24h, amm.volume_24h);
+        assert_eq!(snapshot.last_funding_rate, amm.last_funding_rate);
+    }
+
+    #[test]
+    fn effective_taker_spread() {
+        let amm = AMM {
+            long_spread: (BID_ASK_SPREAD_PRECISION / 100) as u32, // 100 bps
+            short_spread: (BID_ASK_SPREAD_PRECISION / 400) as u32, // 25 bps
+            ..AMM::default()
+        };
+
+        assert_eq!(
+            amm.effective_taker_spread(PositionDirection::Long, 10)
+                .unwrap(),
+            110
+        );
+        assert_eq!(
+            amm.effective_taker_spread(PositionDirection::Short, 10)
+                .unwrap(),
+            35
+        );
+    }
+
+    #[test]
+    fn reserve_price_uninitialized_amm() {
+        let amm = AMM::default();
+        assert_eq!(amm.reserve_price(), Err(ErrorCode::InvalidAmmDetected));
+    }
+
+    #[test]
+    fn bid_ask_price_with_spread() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            long_spread: 1234,
+            short_spread: 567,
+            ..AMM::default()
+        };
+        let reserve_price = amm.reserve_price().unwrap();
+
+        assert_eq!(
+            amm.bid_ask_price_with_spread(reserve_price, amm.long_spread, amm.short_spread)
+                .unwrap(),
+            amm.bid_ask_price(reserve_price).unwrap()
+        );
+
+        let (bid_price, ask_price) = amm
+            .bid_ask_price_with_spread(reserve_price, 10_000, 10_000)
+            .unwrap();
+        assert_eq!(bid_price, 19_800_000);
+        assert_eq!(ask_price, 20_200_000);
+    }
+
+    #[test]
+    fn avg_fill_price() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            long_spread: (BID_ASK_SPREAD_PRECISION / 100) as u32,
+            ..AMM::default()
+        };
+        let (ask_base_asset_reserve, ask_quote_asset_reserve) =
+            crate::math::amm_spread::calculate_spread_reserves(&amm, PositionDirection::Long)
+                .unwrap();
+        amm.ask_base_asset_reserve = ask_base_asset_reserve;
+        amm.ask_quote_asset_reserve = ask_quote_asset_reserve;
+
+        let reserve_price = amm.reserve_price().unwrap();
+        let ask_price = amm.ask_price(reserve_price).unwrap();
+
+        let base_asset_amount = 10 * BASE_PRECISION_I128;
+        let avg_fill_price = amm
+            .avg_fill_price(base_asset_amount, PositionDirection::Long)
+            .unwrap();
+
+        // price at the ask reserves after the fill
+        let mut amm_after = amm;
+        amm_after.base_asset_reserve = ask_base_asset_reserve - 10 * AMM_RESERVE_PRECISION;
+        amm_after.quote_asset_reserve = amm.sqrt_k * amm.sqrt_k / amm_after.base_asset_reserve;
+        let post_trade_price = amm_after.reserve_price().unwrap();
+
+        assert!(ask_price < avg_fill_price);
+        assert!(avg_fill_price < post_trade_price);
+
+        assert!(amm.avg_fill_price(0, PositionDirection::Long).is_err());
+    }
+
+    #[test]
+    fn can_absorb_liquidation() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            ..AMM::default()
+        };
+
+        // selling 0.1 into 100 base reserves moves price ~20 bps
+        assert!(amm
+            .can_absorb_liquidation(BASE_PRECISION_I128 / 10, 50)
+            .unwrap());
+        assert!(amm
+            .can_absorb_liquidation(-BASE_PRECISION_I128 / 10, 50)
+            .unwrap());
+
+        // oversized liquidation
+        assert!(!amm
+            .can_absorb_liquidation(10 * BASE_PRECISION_I128, 50)
+            .unwrap());
+        assert!(!amm
+            .can_absorb_liquidation(-200 * BASE_PRECISION_I128, 50)
+            .unwrap());
+    }
+
+    #[test]
+    fn quote_to_flatten() {
+        // users are net long 10, so the amm is short 10 and its reserves reflect that
+        let mut amm = AMM {
+            base_asset_reserve: 90 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION * 100 / 90,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: PEG_PRECISION,
+            base_asset_amount_with_amm: 10 * BASE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        // buying back 10 moves the quote reserve from 111.11 to 100
+        let quote = amm.quote_to_flatten().unwrap();
+        assert_eq!(quote, 11_111_111);
+
+        amm.base_asset_amount_with_amm = 0;
+        assert_eq!(amm.quote_to_flatten().unwrap(), 0);
+    }
+
+    #[test]
+    fn quote_reserve_for_price() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            ..AMM::default()
+        };
+
+        let target_price = 25_123_456;
+        amm.quote_asset_reserve = amm.quote_reserve_for_price(target_price).unwrap();
+        let price = amm.reserve_price().unwrap();
+        assert!(target_price - price <= 1);
+
+        amm.peg_multiplier = 0;
+        assert!(amm.quote_reserve_for_price(target_price).is_err());
+    }
+
+    #[test]
+    fn set_peg_multiplier() {
+        let mut amm = AMM {
+            peg_multiplier: 20 * PEG_PRECISION,
+            ..AMM::default()
+        };
+
+        assert!(amm.set_peg_multiplier(0).is_err());
+        assert!(amm.set_peg_multiplier(2000 * PEG_PRECISION).is_err());
+        assert_eq!(amm.peg_multiplier, 20 * PEG_PRECISION);
+
+        amm.set_peg_multiplier(40 * PEG_PRECISION).unwrap();
+        assert_eq!(amm.peg_multiplier, 40 * PEG_PRECISION);
+    }
+
+    #[test]
+    fn volatility_spread_component() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            mark_std: 200_000, // 1% of $20
+            ..AMM::default()
+        };
+        let low_vol_component = amm.volatility_spread_component().unwrap();
+        assert_eq!(low_vol_component, (BID_ASK_SPREAD_PRECISION / 200) as u32);
+
+        amm.mark_std = 400_000;
+        let high_vol_component = amm.volatility_spread_component().unwrap();
+        assert_eq!(high_vol_component, (BID_ASK_SPREAD_PRECISION / 100) as u32);
+        assert!(high_vol_component > low_vol_component);
+
+        amm.mark_std = 0;
+        assert_eq!(amm.volatility_spread_component().unwrap(), 0);
+    }
+
+    #[test]
+    fn implied_volatility() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            mark_std: 200_000, // 1% of $20
+            volume_24h: 1_000_000,
+            ..AMM::default()
+        };
+        let low_iv = amm.implied_volatility().unwrap();
+        assert_eq!(low_iv, 935_900); // 93.59%
+
+        amm.mark_std = 400_000;
+        let high_iv = amm.implied_volatility().unwrap();
+        assert_eq!(high_iv, 1_871_800);
+        assert!(high_iv > low_iv);
+
+        amm.volume_24h = 0;
+        assert_eq!(amm.implied_volatility().unwrap(), 0);
+    }
+
+    #[test]
+    fn flow_imbalance() {
+        let mut amm = AMM {
+            long_intensity_volume: 75 * QUOTE_PRECISION_U64,
+            short_intensity_volume: 25 * QUOTE_PRECISION_U64,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.flow_imbalance().unwrap(),
+            (BID_ASK_SPREAD_PRECISION / 2) as i64
+        );
+
+        std::mem::swap(
+            &mut amm.long_intensity_volume,
+            &mut amm.short_intensity_volume,
+        );
+        assert_eq!(
+            amm.flow_imbalance().unwrap(),
+            -((BID_ASK_SPREAD_PRECISION / 2) as i64)
+        );
+
+        let amm = AMM::default();
+        assert_eq!(amm.flow_imbalance().unwrap(), 0);
+    }
+
+    #[test]
+    fn asymmetric_spread() {
+        let mut amm = AMM {
+            long_spread: 1000,
+            short_spread: 1000,
+            max_spread: 10000,
+            long_intensity_volume: 75 * QUOTE_PRECISION_U64,
+            short_intensity_volume: 25 * QUOTE_PRECISION_U64,
+            ..AMM::default()
+        };
+        let (long_spread, short_spread) = amm.asymmetric_spread().unwrap();
+        assert_eq!(long_spread, 1500);
+        assert_eq!(short_spread, 1000);
+        assert!(long_spread > short_spread);
+
+        amm.max_spread = 1200;
+        let (long_spread, short_spread) = amm.asymmetric_spread().unwrap();
+        assert_eq!(long_spread, 1200);
+        assert_eq!(short_spread, 1000);
+
+        // balanced flow leaves the spreads alone
+        amm.short_intensity_volume = amm.long_intensity_volume;
+        assert_eq!(amm.asymmetric_spread().unwrap(), (1000, 1000));
+    }
+
+    #[test]
+    fn spread_adjusted_reserves() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 100 * PEG_PRECISION,
+            short_spread: (BID_ASK_SPREAD_PRECISION / 100) as u32,
+            long_spread: (BID_ASK_SPREAD_PRECISION / 200) as u32,
+            ..AMM::default()
+        };
+        let reserve_price = amm.reserve_price().unwrap();
+        let (bid_price, ask_price) = amm.bid_ask_price(reserve_price).unwrap();
+
+        let (base_asset_reserve, quote_asset_reserve) = amm
+            .spread_adjusted_reserves(PositionDirection::Short)
+            .unwrap();
+        let reserves_bid_price =
+            calculate_price(quote_asset_reserve, base_asset_reserve, amm.peg_multiplier).unwrap();
+        // within a bps of the quoted bid
+        assert!(bid_price.max(reserves_bid_price) - bid_price.min(reserves_bid_price) < 10000);
+        assert!(reserves_bid_price < reserve_price);
+
+        let (base_asset_reserve, quote_asset_reserve) = amm
+            .spread_adjusted_reserves(PositionDirection::Long)
+            .unwrap();
+        let reserves_ask_price =
+            calculate_price(quote_asset_reserve, base_asset_reserve, amm.peg_multiplier).unwrap();
+        assert!(ask_price.max(reserves_ask_price) - ask_price.min(reserves_ask_price) < 10000);
+        assert!(reserves_ask_price > reserve_price);
+    }
+
+    #[test]
+    fn max_base_per_fill() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            max_fill_reserve_fraction: 100,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.max_base_per_fill().unwrap(),
+            AMM_RESERVE_PRECISION as u64
+        );
+
+        amm.max_fill_reserve_fraction = 0;
+        assert!(amm.max_base_per_fill().is_err());
+    }
+
+    #[test]
+    fn inventory_flip_price() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.inventory_flip_price().unwrap(),
+            amm.reserve_price().unwrap()
+        );
+
+        // users short 1 btc, so the amm is neutral once the reserves swap back to 64/64
+        let amm = AMM::default_btc_test();
+        assert!(amm.reserve_price().unwrap() < 19_400 * PRICE_PRECISION_U64);
+        assert_eq!(
+            amm.inventory_flip_price().unwrap(),
+            19_400 * PRICE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn should_update_curve() {
+        let mut amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            curve_update_intensity: 100,
+            ..AMM::default()
+        };
+
+        // reserve price 10 bps above oracle
+        let oracle_price = 19_980_020;
+        assert!(amm.should_update_curve(oracle_price, 5).unwrap());
+        assert!(!amm.should_update_curve(oracle_price, 20).unwrap());
+
+        amm.curve_update_intensity = 0;
+        assert!(!amm.should_update_curve(oracle_price, 5).unwrap());
+    }
+
+    #[test]
+    fn reserve_price_within_oracle_band() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 105 * AMM_RESERVE_PRECISION,
+            sqrt_k: 102469507659,
+            peg_multiplier: PEG_PRECISION,
+            ..AMM::default()
+        };
+
+        // reserve price 5% above oracle
+        assert_eq!(amm.reserve_price().unwrap(), 1_050_000);
+        let oracle_price = PRICE_PRECISION_I64;
+
+        assert!(!amm
+            .reserve_price_within_oracle_band(oracle_price, 300)
+            .unwrap());
+        assert!(amm
+            .reserve_price_within_oracle_band(oracle_price, 500)
+            .unwrap());
+        assert!(amm
+            .reserve_price_within_oracle_band(oracle_price, 600)
+            .unwrap());
+
+        assert!(amm.reserve_price_within_oracle_band(0, 600).is_err());
+    }
+
+    #[test]
+    fn update_volume_24h_saturates() {
+        let now = 1_000_000;
+        let mut amm = AMM {
+            volume_24h: u64::MAX - 1,
+            last_trade_ts: now,
+            ..AMM::default()
+        };
+
+        amm.update_volume_24h(
+            1_000_000_000 * QUOTE_PRECISION_U64,
+            PositionDirection::Long,
+            now,
+        )
+        .unwrap();
+
+        assert_eq!(amm.volume_24h, u64::MAX);
+        assert_eq!(amm.last_trade_ts, now);
+        assert_eq!(
+            amm.long_intensity_volume,
+            1_000_000_000 * QUOTE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn update_volume_24h_ms() {
+        use crate::math::constants::TWENTY_FOUR_HOUR;
+        use crate::math::stats::calculate_rolling_sum;
+
+        let last_trade_ts = 1_000_000;
+        let mut amm = AMM {
+            volume_24h: 1_000_000 * QUOTE_PRECISION_U64,
+            last_trade_ts,
+            ..AMM::default()
+        };
+
+        // first trade lands half way through the second
+        amm.update_volume_24h_ms(
+            QUOTE_PRECISION_U64,
+            PositionDirection::Long,
+            last_trade_ts * 1000 + 500,
+        )
+        .unwrap();
+        assert_eq!(amm.last_trade_ts, last_trade_ts);
+        assert_eq!(amm.last_trade_ts_ms, last_trade_ts * 1000 + 500);
+
+        let mut amm_100ms = amm;
+        amm_100ms
+            .update_volume_24h_ms(
+                QUOTE_PRECISION_U64,
+                PositionDirection::Long,
+                last_trade_ts * 1000 + 600,
+            )
+            .unwrap();
+
+        let mut amm_1s = amm;
+        amm_1s
+            .update_volume_24h_ms(
+                QUOTE_PRECISION_U64,
+                PositionDirection::Long,
+                last_trade_ts * 1000 + 1500,
+            )
+            .unwrap();
+
+        assert!(amm_100ms.volume_24h > amm_1s.volume_24h);
+
+        // decays over the 100ms between the trades, not from the start of the second
+        assert_eq!(
+            amm_100ms.volume_24h,
+            calculate_rolling_sum(
+                amm.volume_24h,
+                QUOTE_PRECISION_U64,
+                100,
+                TWENTY_FOUR_HOUR * 1000
+            )
+            .unwrap()
+        );
+        assert_eq!(amm_100ms.last_trade_ts, last_trade_ts);
+        assert_eq!(amm_100ms.last_trade_ts_ms, last_trade_ts * 1000 + 600);
+        assert_eq!(amm_1s.last_trade_ts, last_trade_ts + 1);
+    }
+
+    #[test]
+    fn fee_tier_for_volume() {
+        let tier_for_volume = |volume_24h: u64| {
+            AMM {
+                volume_24h,
+                ..AMM::default()
+            }
+            .fee_tier_for_volume()
+        };
+
+        assert_eq!(tier_for_volume(0), 0);
+        assert_eq!(tier_for_volume(999_999 * QUOTE_PRECISION_U64), 0);
+        assert_eq!(tier_for_volume(1_000_000 * QUOTE_PRECISION_U64), 1);
+        assert_eq!(tier_for_volume(25_000_000 * QUOTE_PRECISION_U64), 2);
+        assert_eq!(tier_for_volume(50_000_000 * QUOTE_PRECISION_U64), 3);
+        assert_eq!(tier_for_volume(500_000_000 * QUOTE_PRECISION_U64), 4);
+        assert_eq!(tier_for_volume(u64::MAX), 4);
+    }
+
+    #[test]
+    fn funding_payment() {
+        let amm = AMM {
+            cumulative_funding_rate_long: FUNDING_RATE_PRECISION_I128,
+            cumulative_funding_rate_short: FUNDING_RATE_PRECISION_I128 / 2,
+            ..AMM::default()
+        };
+
+        // long pays $1 on positive funding
+        let payment = amm.funding_payment(BASE_PRECISION_I128, 0).unwrap();
+        assert_eq!(payment, -QUOTE_PRECISION_I128);
+
+        // short receives from the short cumulative rate
+        let payment = amm.funding_payment(-BASE_PRECISION_I128, 0).unwrap();
+        assert_eq!(payment, QUOTE_PRECISION_I128 / 2);
+
+        // already settled
+        let payment = amm
+            .funding_payment(BASE_PRECISION_I128, FUNDING_RATE_PRECISION_I128)
+            .unwrap();
+        assert_eq!(payment, 0);
+
+        let payment = amm.funding_payment(0, 0).unwrap();
+        assert_eq!(payment, 0);
+    }
+
+    #[test]
+    fn net_quote_amount() {
+        let amm = AMM {
+            quote_entry_amount_long: -150 * QUOTE_PRECISION_I128,
+            quote_entry_amount_short: 100 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        assert_eq!(amm.net_quote_amount().unwrap(), -50 * QUOTE_PRECISION_I128);
+
+        let amm = AMM {
+            quote_entry_amount_long: -100 * QUOTE_PRECISION_I128,
+            quote_entry_amount_short: 100 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        assert_eq!(amm.net_quote_amount().unwrap(), 0);
+    }
+
+    #[test]
+    fn realized_pnl_to_fees() {
+        let mut amm = AMM::default_btc_test();
+        amm.total_fee = 1_000 * QUOTE_PRECISION_I128;
+        amm.total_mm_fee = 400 * QUOTE_PRECISION_I128;
+        amm.total_exchange_fee = 600 * QUOTE_PRECISION;
+        amm.total_fee_withdrawn = 250 * QUOTE_PRECISION;
+        amm.total_fee_minus_distributions = 500 * QUOTE_PRECISION_I128;
+
+        // 500 + 250 - 1000
+        assert_eq!(
+            amm.realized_pnl_to_fees().unwrap(),
+            -250 * QUOTE_PRECISION_I128
+        );
+
+        amm.total_fee_minus_distributions = 900 * QUOTE_PRECISION_I128;
+        assert_eq!(
+            amm.realized_pnl_to_fees().unwrap(),
+            150 * QUOTE_PRECISION_I128
+        );
+    }
+
+    #[test]
+    fn funding_rate_apr() {
+        let mut amm = AMM {
+            funding_period: 8 * 3600,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: 100 * PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            // 1 bps of the oracle twap per period
+            last_funding_rate: (FUNDING_RATE_PRECISION_I128 / 100) as i64,
+            ..AMM::default()
+        };
+
+        // 1 bps * 3 periods per day * 365 days = 10.95%
+        assert_eq!(amm.funding_rate_apr().unwrap(), 109_500);
+
+        amm.last_funding_rate = -amm.last_funding_rate;
+        assert_eq!(amm.funding_rate_apr().unwrap(), -109_500);
+
+        amm.funding_period = 0;
+        assert!(amm.funding_rate_apr().is_err());
+    }
+
+    #[test]
+    fn amm_inventory_notional() {
+        let mut amm = AMM {
+            base_asset_amount_with_amm: 10 * BASE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        let oracle_price = 25 * PRICE_PRECISION_I64;
+        assert_eq!(
+            amm.amm_inventory_notional(oracle_price).unwrap(),
+            250 * QUOTE_PRECISION_I128
+        );
+
+        amm.base_asset_amount_with_amm = -amm.base_asset_amount_with_amm;
+        assert_eq!(
+            amm.amm_inventory_notional(oracle_price).unwrap(),
+            -250 * QUOTE_PRECISION_I128
+        );
+    }
+
+    #[test]
+    fn amm_leverage() {
+        let mut amm = AMM {
+            base_asset_amount_with_amm: -10 * BASE_PRECISION_I128,
+            total_fee_minus_distributions: 500 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        // 1000 notional / 500 fee pool = 2x
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+        assert_eq!(
+            amm.amm_leverage(oracle_price).unwrap(),
+            2 * MARGIN_PRECISION
+        );
+
+        amm.total_fee_minus_distributions = 0;
+        assert_eq!(amm.amm_leverage(oracle_price).unwrap(), u32::MAX);
+
+        amm.base_asset_amount_with_amm = 0;
+        assert_eq!(amm.amm_leverage(oracle_price).unwrap(), 0);
+    }
+
+    #[test]
+    fn cumulative_funding_delta() {
+        let amm = AMM {
+            cumulative_funding_rate_long: 150 * FUNDING_RATE_PRECISION_I128,
+            cumulative_funding_rate_short: -40 * FUNDING_RATE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        let since_long = 100 * FUNDING_RATE_PRECISION_I128;
+        let since_short = -10 * FUNDING_RATE_PRECISION_I128;
+
+        assert_eq!(
+            amm.cumulative_funding_delta(since_long, since_short, PositionDirection::Long),
+            50 * FUNDING_RATE_PRECISION_I128
+        );
+        assert_eq!(
+            amm.cumulative_funding_delta(since_long, since_short, PositionDirection::Short),
+            -30 * FUNDING_RATE_PRECISION_I128
+        );
+    }
+
+    #[test]
+    fn net_revenue_rate() {
+        let amm = AMM {
+            net_revenue_since_last_funding: 3600 * QUOTE_PRECISION_I64,
+            last_funding_rate_ts: 1000,
+            ..AMM::default()
+        };
+
+        assert_eq!(
+            amm.net_revenue_rate(1000 + 1800).unwrap(),
+            2 * QUOTE_PRECISION_I64
+        );
+        assert_eq!(amm.net_revenue_rate(1000).unwrap(), 0);
+    }
+
+    #[test]
+    fn update_oracle_conf() {
+        let mut amm = AMM {
+            last_oracle_conf_pct: 1000, // 10 bps
+            ..AMM::default()
+        };
+
+        // 10% conf spike 30 seconds later only moves stored conf a tenth of the way
+        amm.update_oracle_conf(100_000, 1000, 1030).unwrap();
+        assert_eq!(amm.last_oracle_conf_pct, 10_901);
+
+        // a second spike in the same second, with the update ts advanced, doesn't compound
+        let mut amm_stale_ts = amm;
+        amm.update_oracle_conf(100_000, 1030, 1030).unwrap();
+        assert_eq!(amm.last_oracle_conf_pct, 10_901);
+
+        // passing the stale ts weights the same 30 seconds in again
+        amm_stale_ts
+            .update_oracle_conf(100_000, 1000, 1030)
+            .unwrap();
+        assert_eq!(amm_stale_ts.last_oracle_conf_pct, 19_811);
+    }
+
+    #[test]
+    fn spread_floor() {
+        let mut amm = AMM {
+            base_spread: 500, // 5 bps
+            last_oracle_conf_pct: 100,
+            ..AMM::default()
+        };
+
+        assert_eq!(amm.spread_floor().unwrap(), 500);
+
+        // 20 bps conf on each side
+        amm.last_oracle_conf_pct = 2000;
+        assert_eq!(amm.spread_floor().unwrap(), 4000);
+    }
+
+    #[test]
+    fn order_improves_amm() {
+        let mut amm = AMM {
+            base_asset_amount_with_amm: 10 * BASE_PRECISION_I128,
+            ..AMM::default()
+        };
+
+        assert!(amm.order_improves_amm(PositionDirection::Short));
+        assert!(!amm.order_improves_amm(PositionDirection::Long));
+
+        amm.base_asset_amount_with_amm = 0;
+        assert!(!amm.order_improves_amm(PositionDirection::Short));
+        assert!(!amm.order_improves_amm(PositionDirection::Long));
+    }
+
+    #[test]
+    fn validate_fee_accounting() {
+        let mut amm = AMM {
+            total_fee: 90 * QUOTE_PRECISION_I128,
+            total_mm_fee: 40 * QUOTE_PRECISION_I128,
+            total_exchange_fee: 60 * QUOTE_PRECISION,
+            total_fee_minus_distributions: 80 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+        assert!(amm.validate_fee_accounting().is_ok());
+
+        // realized amm pnl can push total_fee_minus_distributions past total_fee
+        amm.total_fee_minus_distributions = 95 * QUOTE_PRECISION_I128;
+        assert!(amm.validate_fee_accounting().is_ok());
+
+        amm.total_fee_minus_distributions = 80 * QUOTE_PRECISION_I128;
+        amm.total_exchange_fee = 10 * QUOTE_PRECISION;
+        assert_eq!(
+            amm.validate_fee_accounting(),
+            Err(ErrorCode::InvalidAmmDetected)
+        );
+    }
+
+    #[test]
+    fn total_maker_rebates() {
+        // $50 of spread surplus and $10 of taker fees, but only $30 kept by the market
+        let amm = AMM {
+            total_fee: 30 * QUOTE_PRECISION_I128,
+            total_mm_fee: 50 * QUOTE_PRECISION_I128,
+            total_exchange_fee: 10 * QUOTE_PRECISION,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.total_maker_rebates().unwrap(),
+            30 * QUOTE_PRECISION_I128
+        );
+        assert!(amm.total_maker_rebates().unwrap() > amm.total_exchange_fee as i128);
+
+        assert_eq!(AMM::default().total_maker_rebates().unwrap(), 0);
+    }
+
+    #[test]
+    fn amm_fee_share() {
+        let mut amm = AMM {
+            total_fee: 80 * QUOTE_PRECISION_I128,
+            total_mm_fee: 20 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+        assert_eq!(amm.amm_fee_share().unwrap(), 2500);
+
+        amm.total_mm_fee = -5 * QUOTE_PRECISION_I128;
+        assert_eq!(amm.amm_fee_share().unwrap(), 0);
+
+        amm.total_fee = 0;
+        assert_eq!(amm.amm_fee_share().unwrap(), 0);
+    }
+
+    #[test]
+    fn max_lp_shares_mintable() {
+        let mut amm = AMM {
+            base_asset_amount_long: 90 * BASE_PRECISION_I128,
+            base_asset_amount_short: -80 * BASE_PRECISION_I128,
+            base_asset_amount_with_amm: -10 * BASE_PRECISION_I128,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            user_lp_shares: 10 * AMM_RESERVE_PRECISION,
+            max_open_interest: 100 * AMM_RESERVE_PRECISION,
+            ..AMM::default()
+        };
+
+        // 10 long room and 20 short room caps lps at half the amm, (10 + 80) / (100 + 80)
+        assert_eq!(
+            amm.max_lp_shares_mintable().unwrap(),
+            80 * AMM_RESERVE_PRECISION
+        );
+
+        // near the cap, 1 long room and 11 short room caps lps at 1 / 11 of the amm
+        amm.max_open_interest = 91 * AMM_RESERVE_PRECISION;
+        amm.user_lp_shares = 0;
+        assert_eq!(
+            amm.max_lp_shares_mintable().unwrap(),
+            10 * AMM_RESERVE_PRECISION
+        );
+
+        // existing shares already past the fraction leave nothing to mint
+        amm.user_lp_shares = 10 * AMM_RESERVE_PRECISION;
+        assert_eq!(amm.max_lp_shares_mintable().unwrap(), 0);
+
+        amm.max_open_interest = 0;
+        assert_eq!(amm.max_lp_shares_mintable().unwrap(), u128::MAX);
+    }
+
+    #[test]
+    fn max_lp_shares_mintable_at_cap() {
+        // no amm inventory, but both sides are at the cap
+        let amm = AMM {
+            base_asset_amount_long: 100 * BASE_PRECISION_I128,
+            base_asset_amount_short: -100 * BASE_PRECISION_I128,
+            base_asset_amount_with_amm: 0,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            max_open_interest: 100 * AMM_RESERVE_PRECISION,
+            ..AMM::default()
+        };
+        assert_eq!(amm.max_lp_shares_mintable().unwrap(), 0);
+
+        // one side at the cap
+        let amm = AMM {
+            base_asset_amount_long: 100 * BASE_PRECISION_I128,
+            base_asset_amount_short: -80 * BASE_PRECISION_I128,
+            base_asset_amount_with_amm: -20 * BASE_PRECISION_I128,
+            ..amm
+        };
+        assert_eq!(amm.max_lp_shares_mintable().unwrap(), 0);
+    }
+
+    #[test]
+    fn max_lp_shares_mintable_zero_inventory() {
+        // balanced rooms, lps never add more to a side than the other side's takers opened
+        let amm = AMM {
+            base_asset_amount_long: 50 * BASE_PRECISION_I128,
+            base_asset_amount_short: -50 * BASE_PRECISION_I128,
+            base_asset_amount_with_amm: 0,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            user_lp_shares: 10 * AMM_RESERVE_PRECISION,
+            max_open_interest: 100 * AMM_RESERVE_PRECISION,
+            ..AMM::default()
+        };
+        assert_eq!(amm.max_lp_shares_mintable().unwrap(), u128::MAX);
+    }
+
+    #[test]
+    fn base_to_quote_notional() {
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 20 * PEG_PRECISION,
+            ..AMM::default()
+        };
+
+        // 2.5 base @ $20
+        assert_eq!(
+            amm.base_to_quote_notional(5 * BASE_PRECISION_I128 / 2)
+                .unwrap(),
+            50 * QUOTE_PRECISION_I128
+        );
+        assert_eq!(
+            amm.base_to_quote_notional(-5 * BASE_PRECISION_I128 / 2)
+                .unwrap(),
+            -50 * QUOTE_PRECISION_I128
+        );
+    }
+
+    #[test]
+    fn funding_rate_imbalance() {
+        let amm = AMM {
+            last_funding_rate_long: FUNDING_RATE_PRECISION_I128 as i64,
+            last_funding_rate_short: (FUNDING_RATE_PRECISION_I128 / 4) as i64,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.funding_rate_imbalance().unwrap(),
+            (3 * FUNDING_RATE_PRECISION_I128 / 4) as i64
+        );
+
+        assert_eq!(AMM::default().funding_rate_imbalance().unwrap(), 0);
+    }
+
+    #[test]
+    fn lp_funding_per_share() {
+        let mut amm = AMM {
+            base_asset_amount_per_lp: BASE_PRECISION_I128 / 10,
+            last_funding_rate_long: FUNDING_RATE_PRECISION_I128 as i64,
+            last_funding_rate_short: (FUNDING_RATE_PRECISION_I128 / 2) as i64,
+            ..AMM::default()
+        };
+
+        // long 0.1 per share pays $1 per base
+        assert_eq!(
+            amm.lp_funding_per_share().unwrap(),
+            -QUOTE_PRECISION_I128 / 10
+        );
+
+        // short 0.1 per share receives the short rate
+        amm.base_asset_amount_per_lp = -BASE_PRECISION_I128 / 10;
+        assert_eq!(
+            amm.lp_funding_per_share().unwrap(),
+            QUOTE_PRECISION_I128 / 20
+        );
+    }
+
+    #[test]
+    fn amm_funding_payment() {
+        // users net long 1, so amm is short
+        let mut amm = AMM {
+            base_asset_amount_with_amm: BASE_PRECISION_I128,
+            last_funding_rate: FUNDING_RATE_PRECISION_I128 as i64,
+            ..AMM::default()
+        };
+
+        // positive funding, longs pay the short amm $1
+        assert_eq!(amm.amm_funding_payment().unwrap(), QUOTE_PRECISION_I128);
+
+        amm.last_funding_rate = -(FUNDING_RATE_PRECISION_I128 as i64);
+        assert_eq!(amm.amm_funding_payment().unwrap(), -QUOTE_PRECISION_I128);
+
+        amm.base_asset_amount_with_amm = 0;
+        assert_eq!(amm.amm_funding_payment().unwrap(), 0);
+    }
+
+    #[test]
+    fn can_update_curve() {
+        let mut amm = AMM {
+            last_update_slot: 100,
+            curve_update_intensity: 0,
+            ..AMM::default()
+        };
+
+        // updates disabled
+        assert!(!amm.can_update_curve(1000, 10));
+
+        amm.curve_update_intensity = 100;
+        assert!(!amm.can_update_curve(105, 10));
+        assert!(amm.can_update_curve(110, 10));
+        assert!(amm.can_update_curve(1000, 10));
+
+        // slot behind last update
+        assert!(!amm.can_update_curve(50, 10));
+    }
+
+    #[test]
+    fn mid_twap() {
+        let amm = AMM {
+            last_bid_price_twap: 99 * PRICE_PRECISION_U64,
+            last_ask_price_twap: 102 * PRICE_PRECISION_U64,
+            ..AMM::default()
+        };
+
+        assert_eq!(
+            amm.bid_ask_twap(),
+            (99 * PRICE_PRECISION_U64, 102 * PRICE_PRECISION_U64)
+        );
+        assert_eq!(
+            amm.mid_twap().unwrap(),
+            (99 * PRICE_PRECISION_U64 + 102 * PRICE_PRECISION_U64) / 2
+        );
+    }
+
+    #[test]
+    fn bid_ask_twap_crossed() {
+        let mut amm = AMM {
+            last_bid_price_twap: 99 * PRICE_PRECISION_U64,
+            last_ask_price_twap: 102 * PRICE_PRECISION_U64,
+            ..AMM::default()
+        };
+        assert!(!amm.bid_ask_twap_crossed());
+
+        amm.last_bid_price_twap = 102 * PRICE_PRECISION_U64;
+        assert!(!amm.bid_ask_twap_crossed());
+
+        amm.last_bid_price_twap = 103 * PRICE_PRECISION_U64;
+        assert!(amm.bid_ask_twap_crossed());
+    }
+
+    #[test]
+    fn max_oracle_delay() {
+        let max_delay_for_source = |oracle_source: OracleSource| {
+            AMM {
+                oracle_source,
+                ..AMM::default()
+            }
+            .max_oracle_delay()
+        };
+
+        assert!(
+            max_delay_for_source(OracleSource::Switchboard)
+                > max_delay_for_source(OracleSource::Pyth)
+        );
+        assert_eq!(
+            max_delay_for_source(OracleSource::Pyth1M),
+            max_delay_for_source(OracleSource::Pyth)
+        );
+        assert_eq!(max_delay_for_source(OracleSource::QuoteAsset), i64::MAX);
+    }
+
+    #[test]
+    fn oracle_requires_update() {
+        let requires_update_for_source = |oracle_source: OracleSource| {
+            AMM {
+                oracle_source,
+                ..AMM::default()
+            }
+            .oracle_requires_update()
+        };
+
+        assert!(requires_update_for_source(OracleSource::Prelaunch));
+        assert!(!requires_update_for_source(OracleSource::Pyth));
+        assert!(!requires_update_for_source(OracleSource::Pyth1K));
+        assert!(!requires_update_for_source(OracleSource::Pyth1M));
+        assert!(!requires_update_for_source(OracleSource::PythStableCoin));
+        assert!(!requires_update_for_source(OracleSource::Switchboard));
+        assert!(!requires_update_for_source(OracleSource::QuoteAsset));
+    }
+
+    #[test]
+    fn apply_social_loss() {
+        let mut amm = AMM {
+            total_social_loss: 1_000_000,
+            total_fee_minus_distributions: 50_000_000,
+            ..AMM::default()
+        };
+
+        let socialized = amm.apply_social_loss(20_000_000).unwrap();
+
+        assert_eq!(socialized, 20_000_000);
+        assert_eq!(amm.total_social_loss, 21_000_000);
+        assert_eq!(amm.total_fee_minus_distributions, 30_000_000);
+
+        let socialized = amm.apply_social_loss(0).unwrap();
+
+        assert_eq!(socialized, 0);
+        assert_eq!(amm.total_social_loss, 21_000_000);
+        assert_eq!(amm.total_fee_minus_distributions, 30_000_000);
+    }
+}
+
+mod pool_balance {
+    use crate::math::constants::{ONE_YEAR, SPOT_BALANCE_PRECISION, SPOT_RATE_PRECISION};
+    use crate::state::perp_market::PoolBalance;
+
+    #[test]
+    fn accrue_interest() {
+        let mut pool = PoolBalance {
+            scaled_balance: 1000 * SPOT_BALANCE_PRECISION,
+            market_index: 0,
+            ..PoolBalance::default()
+        };
+
+        // 5% for one year
+        pool.accrue_interest((SPOT_RATE_PRECISION / 20) as u64, ONE_YEAR as i64)
+            .unwrap();
+        assert_eq!(pool.scaled_balance, 1050 * SPOT_BALANCE_PRECISION);
+
+        pool.accrue_interest((SPOT_RATE_PRECISION / 20) as u64, 0)
+            .unwrap();
+        assert_eq!(pool.scaled_balance, 1050 * SPOT_BALANCE_PRECISION);
+    }
+}
+
+mod liquidation_margin_buffer {
+    use crate::state::perp_market::{ContractTier, PerpMarket};
+
+    #[test]
+    fn safer_tiers_have_smaller_buffer() {
+        let buffer_for_tier = |contract_tier: ContractTier| {
+            PerpMarket {
+                contract_tier,
+                ..PerpMarket::default()
+            }
+            .liquidation_margin_buffer()
+        };
+
+        let a = buffer_for_tier(ContractTier::A);
+        let b = buffer_for_tier(ContractTier::B);
+        let c = buffer_for_tier(ContractTier::C);
+        let speculative = buffer_for_tier(ContractTier::Speculative);
+        let isolated = buffer_for_tier(ContractTier::Isolated);
+
+        assert!(a < b);
+        assert!(b < c);
+        assert!(c < speculative);
+        assert!(speculative < isolated);
+    }
+}
+
+mod breakeven_price {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::PerpMarket;
+    use crate::{BASE_PRECISION_I128, PRICE_PRECISION_I64, QUOTE_PRECISION_I128};
+
+    #[test]
+    fn long() {
+        let market = PerpMarket::default_test();
+
+        // long 1 @ $100 with $1 in fees
+        let breakeven_price = market
+            .breakeven_price(
+                BASE_PRECISION_I128,
+                -100 * QUOTE_PRECISION_I128,
+                QUOTE_PRECISION_I128 as u128,
+            )
+            .unwrap();
+
+        assert_eq!(breakeven_price, 101 * PRICE_PRECISION_I64);
+        assert!(breakeven_price > 100 * PRICE_PRECISION_I64);
+    }
+
+    #[test]
+    fn short() {
+        let market = PerpMarket::default_test();
+
+        // short 2 @ $100 with $1 in fees
+        let breakeven_price = market
+            .breakeven_price(
+                -2 * BASE_PRECISION_I128,
+                200 * QUOTE_PRECISION_I128,
+                QUOTE_PRECISION_I128 as u128,
+            )
+            .unwrap();
+
+        assert_eq!(breakeven_price, 99_500_000);
+        assert!(breakeven_price < 100 * PRICE_PRECISION_I64);
+    }
+
+    #[test]
+    fn zero_base() {
+        let market = PerpMarket::default_test();
+
+        let result = market.breakeven_price(0, -100 * QUOTE_PRECISION_I128, 0);
+
+        assert_eq!(result, Err(ErrorCode::UserHasNoPositionInMarket));
+    }
+}
+
+mod set_max_open_interest {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::{AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_U64};
+
+    #[test]
+    fn below_open_interest() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                sqrt_k: 1_000 * AMM_RESERVE_PRECISION,
+                base_asset_amount_long: 100 * BASE_PRECISION_I128,
+                base_asset_amount_short: -50 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let result = market.set_max_open_interest(99 * BASE_PRECISION_U64 as u128);
+
+        assert_eq!(result, Err(ErrorCode::MaxOpenInterest));
+        assert_eq!(market.amm.max_open_interest, 0);
+    }
+
+    #[test]
+    fn too_large_for_sqrt_k() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                sqrt_k: 1_000 * AMM_RESERVE_PRECISION,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let result = market.set_max_open_interest(251 * BASE_PRECISION_U64 as u128);
+
+        assert_eq!(result, Err(ErrorCode::MaxOpenInterest));
+        assert_eq!(market.amm.max_open_interest, 0);
+    }
+
+    #[test]
+    fn success() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                sqrt_k: 1_000 * AMM_RESERVE_PRECISION,
+                base_asset_amount_long: 100 * BASE_PRECISION_I128,
+                base_asset_amount_short: -50 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        market
+            .set_max_open_interest(250 * BASE_PRECISION_U64 as u128)
+            .unwrap();
+
+        assert_eq!(
+            market.amm.max_open_interest,
+            250 * BASE_PRECISION_U64 as u128
+        );
+    }
+
+    #[test]
+    fn zero_removes_cap() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                sqrt_k: 1_000 * AMM_RESERVE_PRECISION,
+                base_asset_amount_long: 100 * BASE_PRECISION_I128,
+                base_asset_amount_short: -50 * BASE_PRECISION_I128,
+                max_open_interest: 250 * BASE_PRECISION_U64 as u128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        market.set_max_open_interest(0).unwrap();
+
+        assert_eq!(market.amm.max_open_interest, 0);
+    }
+}
+
+mod k_adjustment_cost {
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, MAX_CONCENTRATION_COEFFICIENT, PEG_PRECISION,
+    };
+
+    #[test]
+    fn inventory() {
+        // users net long 10, two sides of the curve
+        let market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 90 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 111111111111,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: PEG_PRECISION,
+                concentration_coef: MAX_CONCENTRATION_COEFFICIENT,
+                base_asset_amount_with_amm: 10 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        // decreasing k is a rebate for the protocol
+        let cost = market
+            .k_adjustment_cost(99 * AMM_RESERVE_PRECISION)
+            .unwrap();
+        assert!(cost < 0);
+
+        // increasing k costs the protocol
+        let cost = market
+            .k_adjustment_cost(101 * AMM_RESERVE_PRECISION)
+            .unwrap();
+        assert!(cost > 0);
+
+        let cost = market.k_adjustment_cost(market.amm.sqrt_k).unwrap();
+        assert_eq!(cost, 0);
+
+        // the market itself is left untouched
+        assert_eq!(market.amm.sqrt_k, 100 * AMM_RESERVE_PRECISION);
+    }
+}
+
+mod social_loss_per_base {
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::{BASE_PRECISION_I128, QUOTE_PRECISION, QUOTE_PRECISION_I128};
+
+    #[test]
+    fn spread_over_open_interest() {
+        let market = PerpMarket {
+            amm: AMM {
+                base_asset_amount_long: 50 * BASE_PRECISION_I128,
+                base_asset_amount_short: -40 * BASE_PRECISION_I128,
+                total_social_loss: 100 * QUOTE_PRECISION,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        // $100 over 50 base of open interest
+        assert_eq!(
+            market.social_loss_per_base().unwrap(),
+            2 * QUOTE_PRECISION_I128
+        );
+    }
+
+    #[test]
+    fn no_open_interest() {
+        let market = PerpMarket {
+            amm: AMM {
+                total_social_loss: 100 * QUOTE_PRECISION,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.social_loss_per_base().unwrap(), 0);
+    }
+}
+
+mod worst_case_social_loss {
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::{BASE_PRECISION_I128, PRICE_PRECISION_I64, QUOTE_PRECISION};
+
+    #[test]
+    fn short_amm() {
+        // users net long 100, so amm is short 100
+        let market = PerpMarket {
+            amm: AMM {
+                base_asset_amount_with_amm: 100 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        // 20% move up against the amm @ $100
+        let loss = market
+            .worst_case_social_loss(100 * PRICE_PRECISION_I64, 2000)
+            .unwrap();
+
+        assert_eq!(loss, 2000 * QUOTE_PRECISION);
+    }
+
+    #[test]
+    fn flat_amm() {
+        let market = PerpMarket::default();
+
+        let loss = market
+            .worst_case_social_loss(100 * PRICE_PRECISION_I64, 2000)
+            .unwrap();
+
+        assert_eq!(loss, 0);
+    }
+}
+
+mod insurance_coverage_ratio {
+    use crate::state::perp_market::{InsuranceClaim, PerpMarket, AMM};
+    use crate::{BASE_PRECISION_I128, MARGIN_PRECISION, PRICE_PRECISION_I64, QUOTE_PRECISION_U64};
+
+    #[test]
+    fn insurance_exceeds_worst_case_loss() {
+        // amm short 100 @ $100, 20% stress move loses $2000
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_amount_with_amm: 100 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            insurance_claim: InsuranceClaim {
+                quote_max_insurance: 5000 * QUOTE_PRECISION_U64,
+                ..InsuranceClaim::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let ratio = market
+            .insurance_coverage_ratio(100 * PRICE_PRECISION_I64)
+            .unwrap();
+        assert_eq!(ratio, 5 * MARGIN_PRECISION / 2);
+        assert!(ratio > MARGIN_PRECISION);
+
+        market.insurance_claim.quote_max_insurance = 1000 * QUOTE_PRECISION_U64;
+        let ratio = market
+            .insurance_coverage_ratio(100 * PRICE_PRECISION_I64)
+            .unwrap();
+        assert_eq!(ratio, MARGIN_PRECISION / 2);
+    }
+
+    #[test]
+    fn flat_amm() {
+        let market = PerpMarket::default();
+
+        assert_eq!(
+            market
+                .insurance_coverage_ratio(100 * PRICE_PRECISION_I64)
+                .unwrap(),
+            u32::MAX
+        );
+    }
+}
+
+mod set_amm_paused {
+    use crate::error::ErrorCode;
+    use crate::state::paused_operations::PerpOperation;
+    use crate::state::perp_market::{MarketStatus, PerpMarket};
+
+    #[test]
+    fn round_trip() {
+        let mut market = PerpMarket {
+            status: MarketStatus::Active,
+            paused_operations: PerpOperation::UpdateFunding as u8,
+            ..PerpMarket::default()
+        };
+
+        market.set_amm_paused(true).unwrap();
+        assert!(market.is_operation_paused(PerpOperation::AmmFill));
+        assert!(market.is_operation_paused(PerpOperation::UpdateFunding));
+        assert_eq!(market.status, MarketStatus::Active);
+
+        market.set_amm_paused(false).unwrap();
+        assert!(!market.is_operation_paused(PerpOperation::AmmFill));
+        assert!(market.is_operation_paused(PerpOperation::UpdateFunding));
+        assert_eq!(market.status, MarketStatus::Active);
+    }
+
+    #[test]
+    fn inactive_market() {
+        let mut market = PerpMarket {
+            status: MarketStatus::Settlement,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.set_amm_paused(true), Err(ErrorCode::DefaultError));
+        assert!(!market.is_operation_paused(PerpOperation::AmmFill));
+    }
+}
+
+mod pnl_pool_value {
+    use crate::error::ErrorCode;
+    use crate::math::constants::{QUOTE_PRECISION, SPOT_BALANCE_PRECISION};
+    use crate::state::perp_market::{PerpMarket, PoolBalance};
+    use crate::state::spot_market::SpotMarket;
+
+    #[test]
+    fn quote_value() {
+        let market = PerpMarket {
+            pnl_pool: PoolBalance {
+                scaled_balance: 1000 * SPOT_BALANCE_PRECISION,
+                market_index: 0,
+                ..PoolBalance::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let mut quote_spot_market = SpotMarket::default_quote_market();
+        assert_eq!(
+            market.pnl_pool_value(&quote_spot_market).unwrap(),
+            1000 * QUOTE_PRECISION
+        );
+
+        // cumulative deposit interest of 1.1
+        quote_spot_market.cumulative_deposit_interest =
+            quote_spot_market.cumulative_deposit_interest * 11 / 10;
+        assert_eq!(
+            market.pnl_pool_value(&quote_spot_market).unwrap(),
+            1100 * QUOTE_PRECISION
+        );
+
+        let base_spot_market = SpotMarket::default_base_market();
+        assert_eq!(
+            market.pnl_pool_value(&base_spot_market),
+            Err(ErrorCode::InvalidSpotMarketAccount)
+        );
+    }
+}
+
+mod max_pnl_pool_withdraw {
+    use crate::math::constants::{QUOTE_PRECISION, QUOTE_PRECISION_I128, SPOT_BALANCE_PRECISION};
+    use crate::state::perp_market::{PerpMarket, PoolBalance};
+    use crate::state::spot_market::SpotMarket;
+
+    #[test]
+    fn owed_pnl() {
+        let market = PerpMarket {
+            pnl_pool: PoolBalance {
+                scaled_balance: 1000 * SPOT_BALANCE_PRECISION,
+                market_index: 0,
+                ..PoolBalance::default()
+            },
+            ..PerpMarket::default()
+        };
+        let quote_spot_market = SpotMarket::default_quote_market();
+
+        assert_eq!(
+            market
+                .max_pnl_pool_withdraw(&quote_spot_market, 400 * QUOTE_PRECISION_I128)
+                .unwrap(),
+            600 * QUOTE_PRECISION
+        );
+
+        // owed more than the pool holds
+        assert_eq!(
+            market
+                .max_pnl_pool_withdraw(&quote_spot_market, 1500 * QUOTE_PRECISION_I128)
+                .unwrap(),
+            0
+        );
+
+        // nothing owed
+        assert_eq!(
+            market
+                .max_pnl_pool_withdraw(&quote_spot_market, -100 * QUOTE_PRECISION_I128)
+                .unwrap(),
+            1000 * QUOTE_PRECISION
+        );
+    }
+}
+
+mod total_value_locked {
+    use crate::math::constants::{QUOTE_PRECISION, SPOT_BALANCE_PRECISION};
+    use crate::state::perp_market::{PerpMarket, PoolBalance};
+    use crate::state::spot_market::SpotMarket;
+    use crate::PRICE_PRECISION_I64;
+
+    #[test]
+    fn btc_market() {
+        let mut market = PerpMarket::default_btc_test();
+        market.pnl_pool = PoolBalance {
+            scaled_balance: 1000 * SPOT_BALANCE_PRECISION,
+            market_index: 0,
+            ..PoolBalance::default()
+        };
+        market.amm.fee_pool = PoolBalance {
+            scaled_balance: 500 * SPOT_BALANCE_PRECISION,
+            market_index: 0,
+            ..PoolBalance::default()
+        };
+
+        let quote_spot_market = SpotMarket::default_quote_market();
+        let oracle_price = 20_000 * PRICE_PRECISION_I64;
+
+        // 1000 pnl pool + 500 fee pool + 1 btc of inventory at $20000
+        assert_eq!(
+            market
+                .total_value_locked(&quote_spot_market, oracle_price)
+                .unwrap(),
+            21_500 * QUOTE_PRECISION
+        );
+    }
+}
+
+mod reconcile_fee_pool {
+    use crate::math::constants::{QUOTE_PRECISION, QUOTE_PRECISION_I128};
+    use crate::math::spot_balance::get_token_amount;
+    use crate::state::perp_market::AMM;
+    use crate::state::spot_market::{SpotBalance, SpotMarket};
+
+    #[test]
+    fn empty_fee_pool_pulls_from_backstop() {
+        let mut amm = AMM {
+            total_fee_minus_distributions: 100 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+        let mut quote_spot_market = SpotMarket::default_quote_market();
+
+        assert!(!amm.fee_pool_solvent());
+
+        let amount_pulled = amm
+            .reconcile_fee_pool(&mut quote_spot_market, 40 * QUOTE_PRECISION)
+            .unwrap();
+        assert_eq!(amount_pulled, 40 * QUOTE_PRECISION);
+        assert!(amm.fee_pool_solvent());
+
+        let fee_pool_token_amount = get_token_amount(
+            amm.fee_pool.scaled_balance,
+            &quote_spot_market,
+            amm.fee_pool.balance_type(),
+        )
+        .unwrap();
+        assert_eq!(fee_pool_token_amount, 40 * QUOTE_PRECISION);
+
+        // already solvent, nothing more to pull
+        let amount_pulled = amm
+            .reconcile_fee_pool(&mut quote_spot_market, 40 * QUOTE_PRECISION)
+            .unwrap();
+        assert_eq!(amount_pulled, 0);
+    }
+
+    #[test]
+    fn capped_at_booked_fees() {
+        let mut amm = AMM {
+            total_fee_minus_distributions: 10 * QUOTE_PRECISION_I128,
+            ..AMM::default()
+        };
+        let mut quote_spot_market = SpotMarket::default_quote_market();
+
+        let amount_pulled = amm
+            .reconcile_fee_pool(&mut quote_spot_market, 40 * QUOTE_PRECISION)
+            .unwrap();
+        assert_eq!(amount_pulled, 10 * QUOTE_PRECISION);
+    }
+}
+
+mod validate_new_user {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::PerpMarket;
+
+    #[test]
+    fn at_and_below_cap() {
+        let mut market = PerpMarket {
+            number_of_users: 99,
+            ..PerpMarket::default()
+        };
+
+        assert!(market.validate_new_user(100).is_ok());
+
+        market.number_of_users = 100;
+        assert_eq!(
+            market.validate_new_user(100),
+            Err(ErrorCode::MaxUsersReached)
+        );
+
+        // no cap
+        assert!(market.validate_new_user(0).is_ok());
+    }
+}
+
+mod lp_oi_fraction {
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::{BASE_PRECISION_I128, BID_ASK_SPREAD_PRECISION};
+
+    #[test]
+    fn half_lp() {
+        let market = PerpMarket {
+            amm: AMM {
+                base_asset_amount_long: 100 * BASE_PRECISION_I128,
+                base_asset_amount_short: -80 * BASE_PRECISION_I128,
+                base_asset_amount_with_unsettled_lp: -50 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(
+            market.lp_oi_fraction().unwrap(),
+            (BID_ASK_SPREAD_PRECISION / 2) as u32
+        );
+    }
+
+    #[test]
+    fn zero_open_interest() {
+        let market = PerpMarket {
+            amm: AMM {
+                base_asset_amount_with_unsettled_lp: 50 * BASE_PRECISION_I128,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.lp_oi_fraction().unwrap(), 0);
+    }
+}
+
+mod set_expiry {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::{MarketStatus, PerpMarket};
+
+    #[test]
+    fn past_expiry() {
+        let mut market = PerpMarket {
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.set_expiry(100, 100), Err(ErrorCode::InvalidExpiry));
+        assert_eq!(market.set_expiry(50, 100), Err(ErrorCode::InvalidExpiry));
+        assert_eq!(market.status, MarketStatus::Active);
+        assert_eq!(market.expiry_ts, 0);
+    }
+
+    #[test]
+    fn future_expiry() {
+        let mut market = PerpMarket {
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+
+        market.set_expiry(200, 100).unwrap();
+        assert_eq!(market.status, MarketStatus::ReduceOnly);
+        assert_eq!(market.expiry_ts, 200);
+    }
+}
+
+mod enter_settlement {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::{MarketStatus, PerpMarket};
+    use crate::PRICE_PRECISION_I64;
+
+    #[test]
+    fn from_active() {
+        let mut perp_market = PerpMarket {
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+
+        let settlement_price = 21 * PRICE_PRECISION_I64;
+        perp_market.enter_settlement(settlement_price, 100).unwrap();
+
+        assert_eq!(perp_market.status, MarketStatus::Settlement);
+        assert_eq!(perp_market.expiry_ts, 100);
+        assert_eq!(perp_market.expiry_price, settlement_price);
+    }
+
+    #[test]
+    fn keeps_existing_expiry_ts() {
+        let mut perp_market = PerpMarket {
+            status: MarketStatus::ReduceOnly,
+            expiry_ts: 50,
+            ..PerpMarket::default()
+        };
+
+        perp_market
+            .enter_settlement(21 * PRICE_PRECISION_I64, 100)
+            .unwrap();
+
+        assert_eq!(perp_market.status, MarketStatus::Settlement);
+        assert_eq!(perp_market.expiry_ts, 50);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut perp_market = PerpMarket {
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(
+            perp_market.enter_settlement(0, 100),
+            Err(ErrorCode::InvalidOracle)
+        );
+        assert_eq!(perp_market.status, MarketStatus::Active);
+
+        perp_market.status = MarketStatus::Delisted;
+        assert_eq!(
+            perp_market.enter_settlement(21 * PRICE_PRECISION_I64, 100),
+            Err(ErrorCode::DefaultError)
+        );
+        assert_eq!(perp_market.expiry_price, 0);
+    }
+}
+
+mod remaining_insurance_capacity {
+    use crate::state::perp_market::{InsuranceClaim, PerpMarket};
+    use crate::QUOTE_PRECISION_U64;
+
+    #[test]
+    fn partially_settled() {
+        let market = PerpMarket {
+            insurance_claim: InsuranceClaim {
+                quote_max_insurance: 1000 * QUOTE_PRECISION_U64,
+                quote_settled_insurance: 400 * QUOTE_PRECISION_U64,
+                ..InsuranceClaim::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(
+            market.remaining_insurance_capacity().unwrap(),
+            600 * QUOTE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn fully_settled() {
+        let market = PerpMarket {
+            insurance_claim: InsuranceClaim {
+                quote_max_insurance: 1000 * QUOTE_PRECISION_U64,
+                quote_settled_insurance: 1000 * QUOTE_PRECISION_U64,
+                ..InsuranceClaim::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.remaining_insurance_capacity().unwrap(), 0);
+    }
+}
+
+mod can_delist {
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+    use crate::AMM_RESERVE_PRECISION;
+
+    #[test]
+    fn residual_lp_shares() {
+        let mut market = PerpMarket {
+            status: MarketStatus::Settlement,
+            amm: AMM {
+                user_lp_shares: AMM_RESERVE_PRECISION,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert!(!market.can_delist().unwrap());
+
+        market.amm.user_lp_shares = 0;
+        assert!(market.can_delist().unwrap());
+
+        market.status = MarketStatus::Active;
+        assert!(!market.can_delist().unwrap());
+    }
+}
+
+mod requires_settlement_at_expiry {
+    use crate::state::perp_market::{ContractType, PerpMarket};
+
+    #[test]
+    fn contract_types() {
+        let mut market = PerpMarket {
+            contract_type: ContractType::Perpetual,
+            ..PerpMarket::default()
+        };
+        assert!(!market.requires_settlement_at_expiry());
+
+        market.contract_type = ContractType::Future;
+        assert!(market.requires_settlement_at_expiry());
+    }
+}
+
+mod implied_basis_apr {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::{ContractType, PerpMarket, AMM};
+    use crate::{AMM_RESERVE_PRECISION, ONE_YEAR, PEG_PRECISION};
+
+    #[test]
+    fn future_above_spot() {
+        let now = 1_000_000;
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 20 * PEG_PRECISION,
+                ..AMM::default()
+            },
+            contract_type: ContractType::Future,
+            expiry_ts: now + (ONE_YEAR / 4) as i64,
+            ..PerpMarket::default()
+        };
+
+        // $20 future vs $19.80 spot is ~1.01% basis, a quarter from expiry
+        let basis_apr = market.implied_basis_apr(now, 19_800_000).unwrap();
+        assert_eq!(basis_apr, 40404);
+
+        market.expiry_ts = now;
+        assert_eq!(market.implied_basis_apr(now, 19_800_000).unwrap(), 0);
+
+        market.contract_type = ContractType::Perpetual;
+        assert_eq!(
+            market.implied_basis_apr(now, 19_800_000),
+            Err(ErrorCode::DefaultError)
+        );
+    }
+}
+
+mod fill_oracle_price {
+    use crate::state::oracle::{HistoricalOracleData, OraclePriceData};
+    use crate::state::perp_market::{ContractTier, PerpMarket, AMM};
+    use crate::PRICE_PRECISION_I64;
+
+    #[test]
+    fn spike_clamped_to_band() {
+        let mut market = PerpMarket {
+            amm: AMM {
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price: 100 * PRICE_PRECISION_I64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            contract_tier: ContractTier::A,
+            ..PerpMarket::default()
+        };
+
+        let spike = OraclePriceData {
+            price: 150 * PRICE_PRECISION_I64,
+            ..OraclePriceData::default()
+        };
+
+        // tier A clamps to 10% of the last oracle price
+        assert_eq!(
+            market.fill_oracle_price(&spike).unwrap(),
+            110 * PRICE_PRECISION_I64
+        );
+
+        let within_band = OraclePriceData {
+            price: 95 * PRICE_PRECISION_I64,
+            ..OraclePriceData::default()
+        };
+        assert_eq!(
+            market.fill_oracle_price(&within_band).unwrap(),
+            95 * PRICE_PRECISION_I64
+        );
+
+        // tier C allows up to 50%
+        market.contract_tier = ContractTier::C;
+        assert_eq!(
+            market.fill_oracle_price(&spike).unwrap(),
+            150 * PRICE_PRECISION_I64
+        );
+    }
+}
+
+mod warm_up_complete {
+    use crate::state::perp_market::{MarketStatus, PerpMarket};
+
+    #[test]
+    fn duration_boundary() {
+        let market = PerpMarket {
+            status: MarketStatus::Initialized,
+            warm_up_start_ts: 1000,
+            ..PerpMarket::default()
+        };
+        let warm_up_duration = 60;
+
+        assert!(!market.warm_up_complete(1059, warm_up_duration));
+        assert!(market.warm_up_complete(1060, warm_up_duration));
+        assert!(market.warm_up_complete(2000, warm_up_duration));
+    }
+}
+
+mod initial_margin_requirement {
+    use crate::state::perp_market::PerpMarket;
+    use crate::{BASE_PRECISION_I128, PRICE_PRECISION_I64, QUOTE_PRECISION};
+
+    #[test]
+    fn ten_x() {
+        let market = PerpMarket {
+            margin_ratio_initial: 1000,    // 10x
+            margin_ratio_maintenance: 500, // 20x
+            ..PerpMarket::default()
+        };
+
+        // $1000 notional needs 10%
+        let requirement = market
+            .initial_margin_requirement(10 * BASE_PRECISION_I128, 100 * PRICE_PRECISION_I64)
+            .unwrap();
+        assert_eq!(requirement, 100 * QUOTE_PRECISION);
+
+        let requirement = market
+            .initial_margin_requirement(-10 * BASE_PRECISION_I128, 100 * PRICE_PRECISION_I64)
+            .unwrap();
+        assert_eq!(requirement, 100 * QUOTE_PRECISION);
+    }
+}
+
+mod maintenance_margin_requirement {
+    use crate::state::perp_market::PerpMarket;
+    use crate::{
+        BASE_PRECISION_I128, PRICE_PRECISION_I64, QUOTE_PRECISION, QUOTE_PRECISION_I128,
+        SPOT_WEIGHT_PRECISION,
+    };
+
+    #[test]
+    fn ten_x_near_liquidation() {
+        let market = PerpMarket {
+            margin_ratio_initial: 1000,    // 10x
+            margin_ratio_maintenance: 500, // 20x
+            unrealized_pnl_maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            ..PerpMarket::default()
+        };
+
+        // 100 collateral opened 10 long at $100, price fell to $95
+        let base_asset_amount = 10 * BASE_PRECISION_I128;
+        let oracle_price = 95 * PRICE_PRECISION_I64;
+        let unrealized_pnl = -50 * QUOTE_PRECISION_I128;
+
+        // 47.5 maintenance margin + 50 unrealized loss
+        let requirement = market
+            .maintenance_margin_requirement(base_asset_amount, unrealized_pnl, oracle_price)
+            .unwrap();
+        assert_eq!(requirement, 97_500_000);
+        assert!(requirement < 100 * QUOTE_PRECISION);
+
+        // unrealized profit covers the margin
+        let requirement = market
+            .maintenance_margin_requirement(
+                base_asset_amount,
+                50 * QUOTE_PRECISION_I128,
+                105 * PRICE_PRECISION_I64,
+            )
+            .unwrap();
+        assert_eq!(requirement, 0);
+    }
+}
+
+mod amm_jit_allowed {
+    use crate::state::perp_market::{MarketStatus, PerpMarket, AMM};
+
+    #[test]
+    fn requires_active_market() {
+        let mut market = PerpMarket {
+            status: MarketStatus::AmmPaused,
+            amm: AMM {
+                amm_jit_intensity: 100,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        assert!(!market.amm_jit_allowed());
+
+        market.status = MarketStatus::Active;
+        assert!(market.amm_jit_allowed());
+
+        market.amm.amm_jit_intensity = 0;
+        assert!(!market.amm_jit_allowed());
+    }
+}
+
+mod max_curve_update_intensity {
+    use crate::state::perp_market::{ContractTier, PerpMarket};
+
+    #[test]
+    fn safer_tiers_have_lower_ceilings() {
+        let mut market = PerpMarket {
+            contract_tier: ContractTier::A,
+            ..PerpMarket::default()
+        };
+        let tier_a_ceiling = market.max_curve_update_intensity();
+
+        market.contract_tier = ContractTier::Speculative;
+        let speculative_ceiling = market.max_curve_update_intensity();
+
+        assert!(tier_a_ceiling < speculative_ceiling);
+        assert_eq!(speculative_ceiling, 200);
+    }
+}
+
+mod split_liquidation_fee {
+    use crate::state::perp_market::PerpMarket;
+    use crate::{LIQUIDATION_FEE_PRECISION, QUOTE_PRECISION};
+
+    #[test]
+    fn equal_fees() {
+        let market = PerpMarket {
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: LIQUIDATION_FEE_PRECISION / 100,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(
+            market.split_liquidation_fee(10 * QUOTE_PRECISION).unwrap(),
+            (5 * QUOTE_PRECISION, 5 * QUOTE_PRECISION)
+        );
+    }
+
+    #[test]
+    fn zero_insurance_fee() {
+        let market = PerpMarket {
+            liquidator_fee: LIQUIDATION_FEE_PRECISION / 100,
+            if_liquidation_fee: 0,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(
+            market.split_liquidation_fee(10 * QUOTE_PRECISION).unwrap(),
+            (10 * QUOTE_PRECISION, 0)
+        );
+    }
+}
+
+mod name_str {
+    use crate::state::perp_market::PerpMarket;
+
+    #[test]
+    fn trims_trailing_zeros() {
+        let mut name = [0_u8; 32];
+        name[..8].copy_from_slice(b"SOL-PERP");
+
+        let market = PerpMarket {
+            name,
+            ..PerpMarket::default()
+        };
+
+        assert_eq!(market.name_str(), "SOL-PERP");
+        assert_eq!(PerpMarket::default().name_str(), "");
+    }
+}
+
+mod set_name {
+    use crate::error::ErrorCode;
+    use crate::state::perp_market::PerpMarket;
+
+    #[test]
+    fn exact_length() {
+        let mut market = PerpMarket::default();
+        let name = "ABCDEFGHIJKLMNOPQRSTUVWXYZ-PERP!";
+        assert_eq!(name.len(), 32);
+
+        market.set_name(name).unwrap();
+        assert_eq!(&market.name, name.as_bytes());
+        assert_eq!(market.name_str(), name);
+    }
+
+    #[test]
+    fn short_name_zero_padded() {
+        let mut market = PerpMarket {
+            name: [1; 32],
+            ..PerpMarket::default()
+        };
+
+        market.set_name("SOL-PERP").unwrap();
+        assert_eq!(&market.name[..8], b"SOL-PERP");
+        assert_eq!(market.name[8..], [0; 24]);
+        assert_eq!(market.name_str(), "SOL-PERP");
+    }
+
+    #[test]
+    fn too_long() {
+        let mut market = PerpMarket::default();
+
+        assert_eq!(
+            market.set_name("ABCDEFGHIJKLMNOPQRSTUVWXYZ-PERP-2"),
+            Err(ErrorCode::NameTooLong)
+        );
+        assert_eq!(market.name, [0; 32]);
+    }
+}
+
+mod validate_oracle_account {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::Pubkey;
+
+    use crate::create_account_info;
+    use crate::error::ErrorCode;
+    use crate::state::oracle::OracleSource;
+    use crate::state::perp_market::AMM;
+    use crate::test_utils::*;
+
+    #[test]
+    fn mismatched_key() {
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+
+        let amm = AMM {
+            oracle: oracle_price_key,
+            oracle_source: OracleSource::Pyth,
+            ..AMM::default()
+        };
+        assert!(amm.validate_oracle_account(&oracle_account_info).is_ok());
+
+        let amm = AMM {
+            oracle: Pubkey::default(),
+            oracle_source: OracleSource::Pyth,
+            ..AMM::default()
+        };
+        assert_eq!(
+            amm.validate_oracle_account(&oracle_account_info),
+            Err(ErrorCode::InvalidOracle)
+        );
+    }
 }
@@ -135,6 +135,52 @@ impl Default for OracleGuardRails {
 }
 
 impl OracleGuardRails {
+    pub fn default_conservative() -> Self {
+        OracleGuardRails {
+            price_divergence: PriceDivergenceGuardRails::default(),
+            validity: ValidityGuardRails {
+                slots_before_stale_for_amm: 10,       // ~5 seconds
+                slots_before_stale_for_margin: 60,    // ~30 seconds
+                confidence_interval_max_size: 10_000, // 1% of price
+                too_volatile_ratio: 3,                // 3x or 66% down
+            },
+        }
+    }
+
+    pub fn with_max_divergence(mut self, mark_oracle_percent_divergence: u64) -> Self {
+        self.price_divergence.mark_oracle_percent_divergence = mark_oracle_percent_divergence;
+        self
+    }
+
+    pub fn with_max_twap_5min_divergence(
+        mut self,
+        oracle_twap_5min_percent_divergence: u64,
+    ) -> Self {
+        self.price_divergence.oracle_twap_5min_percent_divergence =
+            oracle_twap_5min_percent_divergence;
+        self
+    }
+
+    pub fn with_validity_slots(
+        mut self,
+        slots_before_stale_for_amm: i64,
+        slots_before_stale_for_margin: i64,
+    ) -> Self {
+        self.validity.slots_before_stale_for_amm = slots_before_stale_for_amm;
+        self.validity.slots_before_stale_for_margin = slots_before_stale_for_margin;
+        self
+    }
+
+    pub fn with_confidence_interval_max_size(mut self, confidence_interval_max_size: u64) -> Self {
+        self.validity.confidence_interval_max_size = confidence_interval_max_size;
+        self
+    }
+
+    pub fn with_too_volatile_ratio(mut self, too_volatile_ratio: i64) -> Self {
+        self.validity.too_volatile_ratio = too_volatile_ratio;
+        self
+    }
+
     pub fn max_oracle_twap_5min_percent_divergence(&self) -> u64 {
         self.price_divergence
             .oracle_twap_5min_percent_divergence
@@ -51,3 +51,52 @@ mod get_init_user_fee {
         assert_eq!(init_user_fee, 1000000000);
     }
 }
+
+mod oracle_guard_rails {
+    use crate::state::state::OracleGuardRails;
+    use crate::PERCENTAGE_PRECISION_U64;
+
+    #[test]
+    fn default_conservative() {
+        let guard_rails = OracleGuardRails::default_conservative();
+        let default_guard_rails = OracleGuardRails::default();
+
+        assert!(
+            guard_rails.validity.slots_before_stale_for_margin
+                <= default_guard_rails.validity.slots_before_stale_for_margin
+        );
+        assert!(
+            guard_rails.validity.confidence_interval_max_size
+                <= default_guard_rails.validity.confidence_interval_max_size
+        );
+        assert!(
+            guard_rails.validity.too_volatile_ratio
+                <= default_guard_rails.validity.too_volatile_ratio
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let guard_rails = OracleGuardRails::default_conservative()
+            .with_max_divergence(PERCENTAGE_PRECISION_U64 / 5)
+            .with_max_twap_5min_divergence(PERCENTAGE_PRECISION_U64 / 4)
+            .with_validity_slots(20, 240)
+            .with_confidence_interval_max_size(30_000)
+            .with_too_volatile_ratio(10);
+
+        assert_eq!(
+            guard_rails.price_divergence.mark_oracle_percent_divergence,
+            PERCENTAGE_PRECISION_U64 / 5
+        );
+        assert_eq!(
+            guard_rails
+                .price_divergence
+                .oracle_twap_5min_percent_divergence,
+            PERCENTAGE_PRECISION_U64 / 4
+        );
+        assert_eq!(guard_rails.validity.slots_before_stale_for_amm, 20);
+        assert_eq!(guard_rails.validity.slots_before_stale_for_margin, 240);
+        assert_eq!(guard_rails.validity.confidence_interval_max_size, 30_000);
+        assert_eq!(guard_rails.validity.too_volatile_ratio, 10);
+    }
+}
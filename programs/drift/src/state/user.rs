@@ -1079,6 +1079,10 @@ impl Order {
         (self.max_ts - now).max(0)
     }
 
+    pub fn is_expired(self, now: i64) -> bool {
+        self.max_ts != 0 && now > self.max_ts
+    }
+
     pub fn has_oracle_price_offset(self) -> bool {
         self.oracle_price_offset != 0
     }
@@ -612,6 +612,7 @@ export type PerpMarketAccount = {
 	quoteSpotMarketIndex: number;
 	feeAdjustment: number;
 	pausedOperations: number;
+	warmUpStartTs: BN;
 };
 
 export type HistoricalOracleData = {
@@ -722,6 +723,7 @@ export type AMM = {
 	lastMarkPriceTwap5Min: BN;
 	lastMarkPriceTwapTs: BN;
 	lastTradeTs: BN;
+	lastTradeTsMs: BN;
 
 	oracle: PublicKey;
 	oracleSource: OracleSource;
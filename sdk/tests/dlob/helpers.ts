@@ -90,6 +90,7 @@ export const mockAMM: AMM = {
 	lastFundingRateLong: new BN(0),
 	concentrationCoef: new BN(0),
 	lastTradeTs: new BN(0),
+	lastTradeTsMs: new BN(0),
 	lastOracleNormalisedPrice: new BN(0),
 	maxOpenInterest: new BN(0),
 	totalLiquidationFee: new BN(0),